@@ -0,0 +1,193 @@
+//! `#[derive(ArenaNode)]`, re-exported by `light-rc-arena` with its `derive` feature.
+
+use proc_macro2::{Span, TokenStream};
+use quote::{format_ident, quote};
+use syn::{
+    Data, DeriveInput, Error, Fields, Ident, Member, Path, Result, Type, parse_macro_input,
+    parse_quote,
+};
+
+/// Implement `ArenaNode` by visiting every field through `ArenaField`, which upholds its safety
+/// contract as long as the fields' impls do.
+///
+/// Fields marked `#[arena(skip)]` hold no references: they are not visited, and are cloned
+/// as they are.
+#[proc_macro_derive(ArenaNode, attributes(arena))]
+pub fn derive_arena_node(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+
+    expand(input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+struct Field {
+    member: Member,
+    binding: Ident,
+    ty: Type,
+    skip: bool,
+}
+
+// A struct, or one variant of an enum.
+struct Shape {
+    path: Path,
+    fields: Vec<Field>,
+}
+
+fn expand(input: DeriveInput) -> Result<TokenStream> {
+    let shapes = match &input.data {
+        Data::Struct(data) => vec![shape(parse_quote!(Self), &data.fields)?],
+        Data::Enum(data) => data
+            .variants
+            .iter()
+            .map(|variant| {
+                let ident = &variant.ident;
+                shape(parse_quote!(Self::#ident), &variant.fields)
+            })
+            .collect::<Result<_>>()?,
+        Data::Union(_) => {
+            return Err(Error::new(
+                Span::call_site(),
+                "ArenaNode can't be derived for unions",
+            ));
+        }
+    };
+
+    let krate = quote!(::light_rc_arena);
+    let n = Ident::new("__ARENA_N", Span::call_site());
+    let field_trait = quote!(#krate::ArenaField<Self, #n>);
+
+    let mut generics = input.generics.clone();
+    generics.params.push(parse_quote!(const #n: usize));
+
+    let where_clause = generics.make_where_clause();
+    for field in shapes.iter().flat_map(|shape| &shape.fields) {
+        let ty = &field.ty;
+        where_clause.predicates.push(if field.skip {
+            parse_quote!(#ty: ::core::clone::Clone)
+        } else {
+            parse_quote!(#ty: #field_trait)
+        });
+    }
+
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = input.generics.split_for_impl();
+    let name = &input.ident;
+
+    let for_each_ref = match_self(&shapes, |shape| {
+        let calls = shape
+            .fields
+            .iter()
+            .filter(|field| !field.skip)
+            .map(|field| {
+                let binding = &field.binding;
+                quote!(<_ as #field_trait>::for_each_field_ref(#binding, f);)
+            });
+        quote!(#(#calls)*)
+    });
+
+    let clone_remapped = match_self(&shapes, |shape| {
+        let path = &shape.path;
+        let values = shape.fields.iter().map(|field| {
+            let (member, binding) = (&field.member, &field.binding);
+            if field.skip {
+                quote!(#member: ::core::clone::Clone::clone(#binding))
+            } else {
+                quote!(#member: <_ as #field_trait>::clone_field_remapped(#binding, map))
+            }
+        });
+        quote!(#path { #(#values),* })
+    });
+
+    let remap_refs = match_self(&shapes, |shape| {
+        let calls = shape
+            .fields
+            .iter()
+            .filter(|field| !field.skip)
+            .map(|field| {
+                let binding = &field.binding;
+                quote!(<_ as #field_trait>::remap_field_refs(#binding, map);)
+            });
+        quote!(#(#calls)*)
+    });
+
+    let arena_ref = quote!(#krate::ArenaRef<Self, #n>);
+    let map = quote!(&mut dyn ::core::ops::FnMut(&#arena_ref) -> #arena_ref);
+
+    Ok(quote! {
+        #[automatically_derived]
+        unsafe impl #impl_generics #krate::ArenaNode<#n> for #name #ty_generics #where_clause {
+            fn for_each_ref(&self, f: &mut dyn ::core::ops::FnMut(&#arena_ref)) {
+                #for_each_ref
+            }
+
+            fn clone_remapped(&self, map: #map) -> Self {
+                #clone_remapped
+            }
+
+            fn remap_refs(&mut self, map: #map) {
+                #remap_refs
+            }
+        }
+    })
+}
+
+fn shape(path: Path, fields: &Fields) -> Result<Shape> {
+    let fields = fields
+        .iter()
+        .enumerate()
+        .map(|(i, field)| {
+            let member = match &field.ident {
+                Some(ident) => Member::Named(ident.clone()),
+                None => Member::Unnamed(i.into()),
+            };
+
+            Ok(Field {
+                member,
+                binding: format_ident!("__field{}", i),
+                ty: field.ty.clone(),
+                skip: is_skipped(&field.attrs)?,
+            })
+        })
+        .collect::<Result<_>>()?;
+
+    Ok(Shape { path, fields })
+}
+
+fn is_skipped(attrs: &[syn::Attribute]) -> Result<bool> {
+    let mut skip = false;
+
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("arena")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `skip`"))
+            }
+        })?;
+    }
+
+    Ok(skip)
+}
+
+// Match on `self`, with one arm per shape binding every field by its position.
+fn match_self(shapes: &[Shape], body: impl Fn(&Shape) -> TokenStream) -> TokenStream {
+    let arms = shapes.iter().map(|shape| {
+        let path = &shape.path;
+        let bindings = shape.fields.iter().map(|field| {
+            let (member, binding) = (&field.member, &field.binding);
+            quote!(#member: #binding)
+        });
+        let body = body(shape);
+
+        quote!(#path { #(#bindings),* } => { #body })
+    });
+
+    // An enum without variants has no arms, and can only be matched on by value.
+    if shapes.is_empty() {
+        quote!(match *self {})
+    } else {
+        quote!(match self { #(#arms)* })
+    }
+}
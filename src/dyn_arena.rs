@@ -0,0 +1,436 @@
+use std::{
+    alloc::Layout,
+    cell::{Cell, RefCell},
+    fmt::{Debug, Display, Formatter},
+    ops::Deref,
+    ptr::NonNull,
+    rc::{Rc, Weak},
+};
+
+struct Chunk {
+    data: NonNull<u8>,
+    layout: Layout,
+}
+
+impl Chunk {
+    fn new(layout: Layout) -> Chunk {
+        let data = NonNull::new(unsafe { std::alloc::alloc(layout) })
+            .unwrap_or_else(|| std::alloc::handle_alloc_error(layout));
+
+        Chunk { data, layout }
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        unsafe { std::alloc::dealloc(self.data.as_ptr(), self.layout) };
+    }
+}
+
+/// How to drop the values of one allocation, as the arena itself doesn't know their type.
+struct DropGlue {
+    ptr: *mut u8,
+    len: usize,
+    drop: unsafe fn(*mut u8, usize),
+}
+
+unsafe fn drop_slice<T>(ptr: *mut u8, len: usize) {
+    unsafe { std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(ptr as *mut T, len)) };
+}
+
+struct DynArenaInner<const N: usize> {
+    // Every chunk, owned by the arena. Values too big for a chunk of `N` bytes get their own.
+    chunks: RefCell<Vec<Chunk>>,
+
+    // Free bytes left in the chunk being filled.
+    cursor: Cell<*mut u8>,
+    end: Cell<*mut u8>,
+
+    // In allocation order, only for values that need dropping.
+    drops: RefCell<Vec<DropGlue>>,
+
+    len: Cell<usize>,
+    allocated_bytes: Cell<usize>,
+}
+
+impl<const N: usize> Drop for DynArenaInner<N> {
+    fn drop(&mut self) {
+        // If a destructor panics, the rest of the values leak, but the chunks are still freed.
+        for glue in std::mem::take(self.drops.get_mut()) {
+            unsafe { (glue.drop)(glue.ptr, glue.len) };
+        }
+    }
+}
+
+impl<const N: usize> DynArenaInner<N> {
+    /// Find room for `layout`, opening a new chunk when the current one is too full.
+    fn alloc_layout(&self, layout: Layout) -> NonNull<u8> {
+        if layout.size() == 0 {
+            // Zero sized values take no room, any aligned pointer will do.
+            return NonNull::new(std::ptr::without_provenance_mut(layout.align())).unwrap();
+        }
+
+        let cursor = self.cursor.get();
+        let padding = (cursor as usize).next_multiple_of(layout.align()) - cursor as usize;
+        let available = self.end.get() as usize - cursor as usize;
+
+        if padding + layout.size() <= available {
+            // SAFETY: it fits in the current chunk.
+            let ptr = unsafe { cursor.add(padding) };
+            self.cursor.set(unsafe { ptr.add(layout.size()) });
+            return unsafe { NonNull::new_unchecked(ptr) };
+        }
+
+        let chunk = Chunk::new(
+            Layout::from_size_align(layout.size().max(N), layout.align())
+                .expect("Allocation is too large!"),
+        );
+        let ptr = chunk.data;
+
+        // A value bigger than a chunk gets one of its own, leaving the current one to fill up.
+        if layout.size() <= N {
+            self.cursor.set(unsafe { ptr.as_ptr().add(layout.size()) });
+            self.end
+                .set(unsafe { ptr.as_ptr().add(chunk.layout.size()) });
+        }
+
+        self.allocated_bytes
+            .set(self.allocated_bytes.get() + chunk.layout.size());
+        self.chunks.borrow_mut().push(chunk);
+        ptr
+    }
+
+    /// Move `len` values from `src` into the arena, remembering how to drop them.
+    ///
+    /// SAFETY: `src` must be valid for `len` reads, and the values must not be used or dropped at
+    ///         the source afterwards (unless they are `Copy`).
+    unsafe fn alloc_copy<T: 'static>(&self, src: *const T, len: usize) -> NonNull<[T]> {
+        let layout = Layout::array::<T>(len).expect("Allocation is too large!");
+        let ptr = self.alloc_layout(layout).cast::<T>();
+
+        unsafe { std::ptr::copy_nonoverlapping(src, ptr.as_ptr(), len) };
+
+        if std::mem::needs_drop::<T>() {
+            self.drops.borrow_mut().push(DropGlue {
+                ptr: ptr.as_ptr() as *mut u8,
+                len,
+                drop: drop_slice::<T>,
+            });
+        }
+
+        self.len.set(self.len.get() + 1);
+        NonNull::slice_from_raw_parts(ptr, len)
+    }
+
+    fn alloc_vec<T: 'static>(&self, mut values: Vec<T>) -> NonNull<[T]> {
+        unsafe {
+            let contents = self.alloc_copy(values.as_ptr(), values.len());
+
+            // The values have been moved into the arena.
+            values.set_len(0);
+            contents
+        }
+    }
+}
+
+/// A reference to a value within a [`DynArena`], which may be unsized like a `str`, a `[T]` or a
+/// `dyn Trait`.
+///
+/// Like an [`crate::ArenaRef`], it does NOT keep the [`DynArena`] alive.
+pub struct DynArenaRef<T: ?Sized, const N: usize> {
+    arena: Weak<DynArenaInner<N>>,
+
+    // SAFETY: ptr MUST be contained within a chunk of arena, or point to a zero sized value.
+    ptr: NonNull<T>,
+}
+
+impl<T: ?Sized, const N: usize> DynArenaRef<T, N> {
+    ///  Try to retrieve the contained value.
+    ///
+    ///  [`None`] corresponds to the parent [`DynArena`] no longer existing
+    pub fn try_get(&self) -> Option<&T> {
+        // SAFETY: According to the 'weak_count' docs: `If no strong pointers remain, this will
+        //         return zero.` So this is a valid check for if the arena is still valid
+        if self.arena.weak_count() == 0 {
+            None
+        } else {
+            Some(unsafe { self.ptr.as_ref() })
+        }
+    }
+
+    ///  Try to retrieve the parent [`DynArena`]. Returns [`None`] when it is no longer alive.
+    pub fn get_arena(&self) -> Option<DynArena<N>> {
+        self.arena.upgrade().map(|inner| DynArena { inner })
+    }
+
+    /// Test if two [`DynArenaRef`]s are pointing to the same values in the same [`DynArena`]s.
+    pub fn ptr_eq<U: ?Sized>(&self, other: &DynArenaRef<U, N>) -> bool {
+        std::ptr::addr_eq(self.ptr.as_ptr(), other.ptr.as_ptr()) && self.arena.ptr_eq(&other.arena)
+    }
+
+    /// Make a reference to part of this value, or to it as another type such as a `dyn Trait`.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`DynArena`] is no longer alive, like [`Deref`].
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    /// use std::fmt::Debug;
+    ///
+    /// let arena = DynArena::<256>::new();
+    /// let pair = arena.alloc((1, "one".to_string()));
+    ///
+    /// let name = pair.map(|(_, name)| name.as_str());
+    /// let debug = pair.map(|pair| pair as &dyn Debug);
+    ///
+    /// assert_eq!(&*name, "one");
+    /// assert_eq!(format!("{:?}", &*debug), r#"(1, "one")"#);
+    /// ```
+    pub fn map<U: ?Sized>(&self, f: impl for<'a> FnOnce(&'a T) -> &'a U) -> DynArenaRef<U, N> {
+        // The reference is either into the value, or lives forever.
+        DynArenaRef {
+            arena: self.arena.clone(),
+            ptr: NonNull::from(f(self)),
+        }
+    }
+}
+
+impl<T: ?Sized, const N: usize> Clone for DynArenaRef<T, N> {
+    fn clone(&self) -> Self {
+        DynArenaRef {
+            arena: self.arena.clone(),
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<T: ?Sized, const N: usize> Deref for DynArenaRef<T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        self.try_get()
+            .expect("The arena associated with this value is no longer valid!")
+    }
+}
+
+impl<T: ?Sized + Debug, const N: usize> Debug for DynArenaRef<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.try_get() {
+            Some(value) => f.debug_tuple("DynArenaRef").field(&value).finish(),
+            None => f.debug_tuple("DynArenaRef").field(&"<dead arena>").finish(),
+        }
+    }
+}
+
+impl<T: ?Sized + Display, const N: usize> Display for DynArenaRef<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.try_get() {
+            Some(value) => std::fmt::Display::fmt(value, f),
+            None => write!(f, "<dead arena reference>"),
+        }
+    }
+}
+
+/// A memory arena for values of any type, that you can pass like an [`Rc`].
+///
+/// Values are bumped into chunks of `N` bytes, and dropped along with the arena, in allocation
+/// order. Values bigger than a chunk get one of their own.
+///
+/// As the arena outlives the types it holds, they have to be `'static`.
+///
+/// Example:
+/// ```
+/// use light_rc_arena::*;
+///
+/// let arena = DynArena::<1024>::new();
+///
+/// let number = arena.alloc(5u8);
+/// let name = arena.alloc_str("five");
+/// let digits = arena.alloc_slice_copy(&[5u64, 5, 5]);
+///
+/// assert_eq!(*number, 5);
+/// assert_eq!(&*name, "five");
+/// assert_eq!(digits.iter().sum::<u64>(), 15);
+/// ```
+pub struct DynArena<const N: usize = 4096> {
+    inner: Rc<DynArenaInner<N>>,
+}
+
+impl<const N: usize> DynArena<N> {
+    /// Create a new DynArena
+    pub fn new() -> DynArena<N> {
+        assert!(N > 0, "Using zero for chunk size is illegal!");
+
+        DynArena {
+            inner: Rc::new(DynArenaInner {
+                chunks: RefCell::new(Vec::new()),
+                cursor: Cell::new(std::ptr::null_mut()),
+                end: Cell::new(std::ptr::null_mut()),
+                drops: RefCell::new(Vec::new()),
+                len: Cell::new(0),
+                allocated_bytes: Cell::new(0),
+            }),
+        }
+    }
+
+    fn make_ref<T: ?Sized>(&self, ptr: NonNull<T>) -> DynArenaRef<T, N> {
+        DynArenaRef {
+            arena: Rc::downgrade(&self.inner),
+            ptr,
+        }
+    }
+
+    /// Move an object into the arena, and return a [`DynArenaRef`] to its new location.
+    pub fn alloc<T: 'static>(&self, cont: T) -> DynArenaRef<T, N> {
+        let cont = std::mem::ManuallyDrop::new(cont);
+
+        // SAFETY: `cont` is never dropped here.
+        let ptr = unsafe { self.inner.alloc_copy(&*cont as *const T, 1) };
+        self.make_ref(ptr.cast::<T>())
+    }
+
+    /// Copy a string into the arena.
+    pub fn alloc_str(&self, src: &str) -> DynArenaRef<str, N> {
+        let bytes = self.alloc_slice_copy(src.as_bytes());
+
+        // SAFETY: the bytes were copied from a `str`.
+        self.make_ref(NonNull::new(bytes.ptr.as_ptr() as *mut str).unwrap())
+    }
+
+    /// Copy a slice into the arena, and return a [`DynArenaRef`] to it.
+    pub fn alloc_slice_copy<T: Copy + 'static>(&self, src: &[T]) -> DynArenaRef<[T], N> {
+        // SAFETY: `T: Copy`, so the source stays valid.
+        let ptr = unsafe { self.inner.alloc_copy(src.as_ptr(), src.len()) };
+        self.make_ref(ptr)
+    }
+
+    /// Clone a slice into the arena, and return a [`DynArenaRef`] to it.
+    ///
+    /// See [`DynArena::alloc_extend`].
+    pub fn alloc_slice_clone<T: Clone + 'static>(&self, src: &[T]) -> DynArenaRef<[T], N> {
+        self.alloc_extend(src.iter().cloned())
+    }
+
+    /// Move every value of an iterator into the arena as one slice, and return a
+    /// [`DynArenaRef`] to it.
+    ///
+    /// The values are collected before being moved in, so the iterator is free to allocate in
+    /// this [`DynArena`] itself.
+    pub fn alloc_extend<T: 'static, I: IntoIterator<Item = T>>(
+        &self,
+        iter: I,
+    ) -> DynArenaRef<[T], N> {
+        let ptr = self.inner.alloc_vec(iter.into_iter().collect());
+        self.make_ref(ptr)
+    }
+
+    /// Number of allocations in the arena, counting a slice or a string as one.
+    pub fn len(&self) -> usize {
+        self.inner.len.get()
+    }
+
+    /// Returns `true` if nothing has been allocated in the arena.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Bytes held by the chunks of the arena.
+    pub fn allocated_bytes(&self) -> usize {
+        self.inner.allocated_bytes.get()
+    }
+}
+
+impl<const N: usize> Clone for DynArena<N> {
+    fn clone(&self) -> Self {
+        DynArena {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<const N: usize> Default for DynArena<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> PartialEq for DynArena<N> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fmt::Debug;
+
+    #[test]
+    fn mixed_types() {
+        let arena: DynArena<64> = DynArena::new();
+
+        let a = arena.alloc(1u8);
+        let b = arena.alloc(2u64);
+        let c = arena.alloc_str("three");
+        let d = arena.alloc([4u128; 8]);
+        let e = arena.alloc(());
+        let f = arena.alloc_slice_copy::<u16>(&[]);
+
+        assert_eq!(
+            (*a, *b, &*c, *d, *e, &*f),
+            (1, 2, "three", [4; 8], (), &[][..])
+        );
+        assert_eq!(b.ptr.as_ptr() as usize % align_of::<u64>(), 0);
+        assert_eq!(d.ptr.as_ptr() as usize % align_of::<u128>(), 0);
+        assert_eq!(arena.len(), 6);
+
+        // The big array got a chunk of its own, the next values still go in the first one.
+        let g = arena.alloc(7u8);
+        assert_eq!(
+            g.ptr.as_ptr() as usize,
+            c.ptr.as_ptr() as *const u8 as usize + 5
+        );
+        assert_eq!(arena.allocated_bytes(), 64 + 128);
+
+        drop(arena);
+        assert!(a.try_get().is_none());
+        assert!(c.try_get().is_none());
+    }
+
+    #[test]
+    fn drops_values() {
+        let counter = Rc::new(());
+        let arena: DynArena<32> = DynArena::new();
+
+        arena.alloc(counter.clone());
+        arena.alloc(vec![counter.clone(); 3]);
+        arena.alloc_slice_clone(&[counter.clone(), counter.clone()]);
+        arena.alloc_extend((0..20).map(|_| counter.clone()));
+        assert_eq!(Rc::strong_count(&counter), 27);
+
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn unsized_refs() {
+        let arena: DynArena = DynArena::new();
+
+        let values: Vec<DynArenaRef<dyn Debug, 4096>> = vec![
+            arena.alloc(1).map(|x| x as &dyn Debug),
+            arena.alloc("two").map(|x| x as &dyn Debug),
+            arena.alloc([3, 3]).map(|x| x as &dyn Debug),
+        ];
+
+        let printed: Vec<_> = values.iter().map(|x| format!("{:?}", &**x)).collect();
+        assert_eq!(printed, ["1", "\"two\"", "[3, 3]"]);
+
+        let slice = arena.alloc_slice_copy(&[1, 2, 3]);
+        let tail = slice.map(|s| &s[1..]);
+        assert_eq!(&*tail, &[2, 3]);
+        assert!(!tail.ptr_eq(&slice));
+        assert!(slice.map(|s| &s[0]).ptr_eq(&slice));
+    }
+}
@@ -0,0 +1,151 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    hash::{Hash, Hasher},
+};
+
+use crate::{Arena, ArenaIndex, ArenaIterator, ArenaRef, ByValue};
+
+/// An [`Arena`] that stores every distinct value once, also known as hash-consing.
+///
+/// Allocating a value equal to one already in the arena hands out the existing [`ArenaRef`], so
+/// for values built out of other refs from the same arena, [`ArenaRef::ptr_eq`] is the same as
+/// comparing them structurally.
+///
+/// Such values should hold their children as [`ByAddress`](crate::ByAddress). Equal children are
+/// the same ref already, and a plain [`ArenaRef`] would hash and compare the whole tree below it
+/// instead.
+///
+/// Example:
+/// ```
+/// use light_rc_arena::*;
+///
+/// let terms = HashConsArena::<(char, u32)>::new();
+///
+/// let a = terms.alloc(('x', 1));
+/// let b = terms.alloc(('x', 1));
+/// let c = terms.alloc(('y', 1));
+///
+/// assert!(a.ptr_eq(&b));
+/// assert!(!a.ptr_eq(&c));
+/// assert_eq!(terms.len(), 2);
+/// ```
+pub struct HashConsArena<T: Eq + Hash, const N: usize = 64> {
+    arena: Arena<T, N>,
+    values: RefCell<HashSet<ByValue<ArenaRef<T, N>>>>,
+}
+
+impl<T: Eq + Hash, const N: usize> HashConsArena<T, N> {
+    /// Create a new HashConsArena
+    pub fn new() -> HashConsArena<T, N> {
+        HashConsArena {
+            arena: Arena::new(),
+            values: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Move a value into the arena and return an [`ArenaRef`] to it, or drop it and return the
+    /// [`ArenaRef`] to an equal value already there.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the `Hash` or `Eq` implementation of `T` calls back into this arena.
+    pub fn alloc(&self, cont: T) -> ArenaRef<T, N> {
+        if let Some(existing) = self.find(&cont) {
+            return existing;
+        }
+
+        let r = self.arena.alloc(cont);
+        self.values.borrow_mut().insert(ByValue(r.clone()));
+        r
+    }
+
+    /// Get the [`ArenaRef`] to a value equal to `value`, if there is one.
+    pub fn find(&self, value: &T) -> Option<ArenaRef<T, N>> {
+        let values = self.values.borrow();
+        values.get(value).map(|key| key.0.clone())
+    }
+
+    /// Get the value at `index`, see [`Arena::get`].
+    pub fn get(&self, index: ArenaIndex<T>) -> Option<&T> {
+        self.arena.get(index)
+    }
+
+    /// Create an iterator over every distinct value, in the order they were first allocated.
+    pub fn iter(&self) -> ArenaIterator<T, N> {
+        self.arena.iter()
+    }
+
+    /// Number of distinct values in the arena.
+    pub fn len(&self) -> usize {
+        self.values.borrow().len()
+    }
+
+    /// Returns `true` if nothing has been allocated in the arena.
+    pub fn is_empty(&self) -> bool {
+        self.values.borrow().is_empty()
+    }
+}
+
+impl<T: Eq + Hash, const N: usize> Default for HashConsArena<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ByAddress;
+    use std::rc::Rc;
+
+    #[derive(PartialEq, Eq, Hash)]
+    enum Term {
+        Var(char),
+        Pair(ByAddress<Term, 4>, ByAddress<Term, 4>),
+    }
+
+    #[test]
+    fn equal_values_share_a_slot() {
+        let terms: HashConsArena<Term, 4> = HashConsArena::new();
+
+        // Equal children are the same ref, so only their addresses need to be compared.
+        let build = |depth: usize| {
+            let mut term = terms.alloc(Term::Var('x'));
+            for _ in 0..depth {
+                let child = ByAddress(term);
+                term = terms.alloc(Term::Pair(child.clone(), child));
+            }
+            term
+        };
+
+        let a = build(10);
+        let b = build(10);
+        let c = build(5);
+
+        assert!(a.ptr_eq(&b));
+        assert!(!a.ptr_eq(&c));
+        assert_eq!(terms.len(), 11);
+
+        let first = ByAddress(terms.iter().next().unwrap());
+        let second = terms.find(&Term::Pair(first.clone(), first)).unwrap();
+        assert!(second.ptr_eq(&terms.iter().nth(1).unwrap()));
+        assert!(matches!(terms.get(second.index()), Some(Term::Pair(..))));
+        assert!(terms.find(&Term::Var('y')).is_none());
+    }
+
+    #[test]
+    fn duplicates_are_dropped() {
+        let counter = Rc::new(());
+        let values: HashConsArena<(u8, Rc<()>)> = HashConsArena::new();
+
+        // Only the first copy is kept.
+        for _ in 0..10 {
+            values.alloc((1, counter.clone()));
+        }
+        assert_eq!(Rc::strong_count(&counter), 2);
+
+        drop(values);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+}
@@ -0,0 +1,271 @@
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fmt::{Debug, Display, Formatter},
+    hash::{Hash, Hasher},
+    ops::Deref,
+};
+
+use crate::{Arena, ArenaSlice, ByValue};
+
+/// A reference to a string within an [`Arena`] of bytes, see [`Arena::alloc_str`].
+///
+/// Like an [`crate::ArenaRef`], it does NOT keep the [`Arena`] alive.
+pub struct ArenaStr<const N: usize> {
+    // SAFETY: always valid UTF-8.
+    bytes: ArenaSlice<u8, N>,
+}
+
+impl<const N: usize> ArenaStr<N> {
+    ///  Try to retrieve the string.
+    ///
+    ///  [`None`] corresponds to the parent [`Arena`] no longer existing, or having been
+    ///  [reset](Arena::reset) since this was handed out.
+    pub fn try_get(&self) -> Option<&str> {
+        let bytes = self.bytes.try_get()?;
+        Some(unsafe { std::str::from_utf8_unchecked(bytes) })
+    }
+
+    ///  Try to retrive the parent [`Arena`]. Returns [`None`] when it is no longer alive,
+    ///  or has been [reset](Arena::reset) since this was handed out.
+    pub fn get_arena(&self) -> Option<Arena<u8, N>> {
+        self.bytes.get_arena()
+    }
+
+    /// Test if two [`ArenaStr`]s are pointing to the same string in the same [`Arena`]s.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.bytes.ptr_eq(&other.bytes)
+    }
+}
+
+impl<const N: usize> Clone for ArenaStr<N> {
+    fn clone(&self) -> Self {
+        ArenaStr {
+            bytes: self.bytes.clone(),
+        }
+    }
+}
+
+impl<const N: usize> Deref for ArenaStr<N> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        self.try_get()
+            .expect("The arena assosiated with this value is no longer valid!")
+    }
+}
+
+impl<const N: usize> Debug for ArenaStr<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.try_get() {
+            Some(value) => f.debug_tuple("ArenaStr").field(&value).finish(),
+            None => f.debug_tuple("ArenaStr").field(&"<dead arena>").finish(),
+        }
+    }
+}
+
+impl<const N: usize> Display for ArenaStr<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.try_get() {
+            Some(value) => std::fmt::Display::fmt(value, f),
+            None => write!(f, "<dead arena reference>"),
+        }
+    }
+}
+
+impl<const N: usize> Arena<u8, N> {
+    /// Copy a string into the arena as one contiguous run, and return an [`ArenaStr`] to it.
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let arena = Arena::<u8, 1024>::new();
+    /// let hello = arena.alloc_str("hello");
+    ///
+    /// assert_eq!(&*hello, "hello");
+    /// assert_eq!(arena.len(), 5);
+    /// ```
+    pub fn alloc_str(&self, src: &str) -> ArenaStr<N> {
+        ArenaStr {
+            bytes: self.alloc_slice_copy(src.as_bytes()),
+        }
+    }
+}
+
+/// A string interned by an [`Interner`].
+///
+/// Equal strings from the same [`Interner`] share one [`Symbol`], so comparing and hashing
+/// them only looks at the pointer.
+pub struct Symbol<const N: usize> {
+    text: ArenaStr<N>,
+}
+
+impl<const N: usize> Symbol<N> {
+    ///  Try to retrieve the string. [`None`] corresponds to the [`Interner`] no longer existing.
+    pub fn try_get(&self) -> Option<&str> {
+        self.text.try_get()
+    }
+}
+
+impl<const N: usize> Clone for Symbol<N> {
+    fn clone(&self) -> Self {
+        Symbol {
+            text: self.text.clone(),
+        }
+    }
+}
+
+impl<const N: usize> PartialEq for Symbol<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.text.ptr_eq(&other.text)
+    }
+}
+
+impl<const N: usize> Eq for Symbol<N> {}
+
+impl<const N: usize> Hash for Symbol<N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.text.bytes.ptr.hash(state);
+    }
+}
+
+impl<const N: usize> Deref for Symbol<N> {
+    type Target = str;
+
+    fn deref(&self) -> &Self::Target {
+        &self.text
+    }
+}
+
+impl<const N: usize> Debug for Symbol<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.try_get() {
+            Some(value) => f.debug_tuple("Symbol").field(&value).finish(),
+            None => f.debug_tuple("Symbol").field(&"<dead arena>").finish(),
+        }
+    }
+}
+
+impl<const N: usize> Display for Symbol<N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&self.text, f)
+    }
+}
+
+/// Deduplicates strings, storing each one once in an [`Arena`] of bytes.
+///
+/// Like an [`Arena`], the [`Symbol`]s it hands out do NOT keep it alive.
+///
+/// Example:
+/// ```
+/// use light_rc_arena::*;
+///
+/// let interner = Interner::<1024>::new();
+///
+/// let a = interner.intern("main");
+/// let b = interner.intern(&String::from("main"));
+/// let c = interner.intern("exit");
+///
+/// assert_eq!(a, b);
+/// assert_ne!(a, c);
+/// assert_eq!(&*a, "main");
+/// assert_eq!(interner.len(), 2);
+/// ```
+pub struct Interner<const N: usize = 4096> {
+    arena: Arena<u8, N>,
+    symbols: RefCell<HashSet<ByValue<ArenaStr<N>>>>,
+}
+
+impl<const N: usize> Interner<N> {
+    /// Create a new Interner
+    pub fn new() -> Interner<N> {
+        Interner {
+            arena: Arena::new(),
+            symbols: RefCell::new(HashSet::new()),
+        }
+    }
+
+    /// Get the [`Symbol`] for a string, copying it into the arena the first time it is seen.
+    pub fn intern(&self, text: &str) -> Symbol<N> {
+        if let Some(symbol) = self.get(text) {
+            return symbol;
+        }
+
+        let text = self.arena.alloc_str(text);
+        self.symbols.borrow_mut().insert(ByValue(text.clone()));
+        Symbol { text }
+    }
+
+    /// Get the [`Symbol`] for a string, if it has been interned.
+    pub fn get(&self, text: &str) -> Option<Symbol<N>> {
+        let symbols = self.symbols.borrow();
+        let key = symbols.get(text)?;
+
+        Some(Symbol {
+            text: key.0.clone(),
+        })
+    }
+
+    /// Number of distinct strings interned.
+    pub fn len(&self) -> usize {
+        self.symbols.borrow().len()
+    }
+
+    /// Returns `true` if no string has been interned yet.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl<const N: usize> Default for Interner<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn strings_live_in_the_arena() {
+        let arena: Arena<u8, 8> = Arena::new();
+
+        let short = arena.alloc_str("abc");
+        let long = arena.alloc_str("longer than a segment");
+        let empty = arena.alloc_str("");
+
+        assert_eq!(
+            (&*short, &*long, &*empty),
+            ("abc", "longer than a segment", "")
+        );
+        assert!(short.clone().ptr_eq(&short));
+        assert_eq!(format!("{short} {short:?}"), r#"abc ArenaStr("abc")"#);
+
+        drop(arena);
+        assert!(long.try_get().is_none());
+    }
+
+    #[test]
+    fn interning() {
+        let interner: Interner<16> = Interner::new();
+
+        let words = "a rose is a rose is a rose".split(' ');
+        let symbols: Vec<_> = words.clone().map(|word| interner.intern(word)).collect();
+
+        assert_eq!(interner.len(), 3);
+        assert_eq!(interner.arena.len(), "aroseis".len());
+        assert!(words.eq(symbols.iter().map(|symbol| &**symbol)));
+
+        assert_eq!(symbols[0], symbols[3]);
+        assert!(std::ptr::eq(symbols[1].as_ptr(), symbols[4].as_ptr()));
+        assert_ne!(symbols[0], symbols[1]);
+
+        let set: HashSet<_> = symbols.into_iter().collect();
+        assert_eq!(set.len(), 3);
+
+        assert_eq!(interner.get("rose").as_deref(), Some("rose"));
+        assert_eq!(interner.get("tulip"), None);
+    }
+}
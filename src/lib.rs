@@ -2,56 +2,204 @@
 #![allow(unused)]
 
 use std::{
-    cell::{Cell, UnsafeCell},
+    alloc::Layout,
+    cell::Cell,
+    cell::RefCell,
+    collections::HashMap,
     fmt::{Debug, Display, Formatter},
-    mem::MaybeUninit,
+    hash::{Hash, Hasher},
+    iter::FusedIterator,
+    marker::PhantomData,
     ops::Deref,
-    ptr::addr_of_mut,
+    ptr::NonNull,
     rc::{Rc, Weak},
 };
 
-struct Segment<T, const N: usize> {
+// Lets `#[derive(ArenaNode)]` name this crate from within it.
+extern crate self as light_rc_arena;
+
+mod dyn_arena;
+mod hash_cons;
+mod interner;
+mod node;
+#[cfg(feature = "serde")]
+mod serde;
+mod sync;
+
+pub use dyn_arena::{DynArena, DynArenaRef};
+pub use hash_cons::HashConsArena;
+pub use interner::{ArenaStr, Interner, Symbol};
+#[cfg(feature = "derive")]
+pub use light_rc_arena_derive::ArenaNode;
+pub use node::{ArenaField, ArenaNode};
+pub use sync::{SyncArena, SyncArenaGuard, SyncArenaIterator, SyncArenaRef};
+
+// Every slot has a stamp, which is even while the slot is vacant and odd while it holds a value.
+// It is bumped whenever that changes, so a handle can tell if its value is still the one there.
+
+// Stamp of a slot that is part of a run from `alloc_slice_*`, which is never removed on its own.
+const PINNED: u32 = u32::MAX;
+
+// Stamp of a slot that is vacant for good, as its next stamp would repeat an old one.
+const RETIRED: u32 = u32::MAX - 1;
+
+struct Segment<T> {
     length: Cell<usize>,
 
-    next: Cell<Option<Box<Segment<T, N>>>>,
+    // Values held below `length`, fewer than it once some have been removed.
+    live: Cell<usize>,
+
+    // Index of the first slot within the whole arena, see `ArenaIndex`.
+    base: Cell<usize>,
+
+    // Usually `N`, but runs of values longer than that get a segment of their own.
+    capacity: usize,
 
-    data: [UnsafeCell<MaybeUninit<T>>; N],
+    // SAFETY: points to `capacity` slots. Those below `length` with an odd stamp are
+    //         initialized.
+    data: NonNull<T>,
+
+    // One per slot, only meaningful below `length`.
+    stamps: Box<[Cell<u32>]>,
+
+    // Slots of this segment listed in `ArenaInner::late`.
+    late: Cell<usize>,
 }
 
-impl<T, const N: usize> Segment<T, N> {
-    fn new() -> Box<Segment<T, N>> {
-        // Create the Segment on the heap. As larger N values can oversaturate the stack
-        unsafe {
-            let layout = std::alloc::Layout::new::<Segment<T, N>>();
-            let ptr = std::alloc::alloc(layout) as *mut Segment<T, N>;
+/// Where a value lives within an arena.
+struct Slot<T> {
+    index: usize,
+    ptr: *mut T,
+    stamp: *const Cell<u32>,
+}
 
-            // Handle OOMs
-            if ptr.is_null() {
-                std::alloc::handle_alloc_error(layout);
-            }
+impl<T> Clone for Slot<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for Slot<T> {}
+
+impl<T> Segment<T> {
+    fn new(capacity: usize, base: usize) -> Box<Segment<T>> {
+        Self::try_new(capacity, base).unwrap_or_else(|| match Layout::array::<T>(capacity) {
+            Ok(layout) => std::alloc::handle_alloc_error(layout),
+            Err(_) => panic!("Segment is too large!"),
+        })
+    }
+
+    /// Like [`Segment::new`], but returns [`None`] instead of aborting when out of memory.
+    fn try_new(capacity: usize, base: usize) -> Option<Box<Segment<T>>> {
+        // The slots live in their own heap allocation. As larger N values can oversaturate the
+        // stack, and it lets a segment have any capacity.
+        let layout = Layout::array::<T>(capacity).ok()?;
+
+        let mut stamps = Vec::new();
+        stamps.try_reserve_exact(capacity).ok()?;
+        stamps.resize_with(capacity, || Cell::new(0));
+
+        let data = if layout.size() == 0 {
+            NonNull::dangling()
+        } else {
+            NonNull::new(unsafe { std::alloc::alloc(layout) } as *mut T)?
+        };
+
+        // SAFETY: `data` does NOT need initialized due to the length being 0.
+        Some(Box::new(Segment {
+            length: Cell::new(0),
+            live: Cell::new(0),
+            base: Cell::new(base),
+            capacity,
+            data,
+            stamps: stamps.into_boxed_slice(),
+            late: Cell::new(0),
+        }))
+    }
+
+    /// Bytes taken by a segment with `capacity` slots, counting its header.
+    fn size_for(capacity: usize) -> Option<usize> {
+        Layout::array::<T>(capacity)
+            .ok()?
+            .size()
+            .checked_add(Layout::array::<Cell<u32>>(capacity).ok()?.size())?
+            .checked_add(std::mem::size_of::<Segment<T>>())
+    }
+
+    /// Free slots left at the end of this segment.
+    #[inline]
+    fn remaining(&self) -> usize {
+        self.capacity - self.length.get()
+    }
+
+    /// Index of the first slot after this segment.
+    #[inline]
+    fn end(&self) -> usize {
+        self.base.get() + self.capacity
+    }
+
+    #[inline]
+    fn ptr(&self, i: usize) -> *mut T {
+        debug_assert!(i <= self.capacity);
+        unsafe { self.data.as_ptr().add(i) }
+    }
+
+    #[inline]
+    fn slot(&self, i: usize) -> Slot<T> {
+        Slot {
+            index: self.base.get() + i,
+            ptr: self.ptr(i),
+            stamp: unsafe { self.stamps.as_ptr().add(i) },
+        }
+    }
+
+    #[inline]
+    fn is_occupied(&self, i: usize) -> bool {
+        self.stamps[i].get() % 2 == 1
+    }
 
-            addr_of_mut!((*ptr).length).write(Cell::new(0));
-            addr_of_mut!((*ptr).next).write(Cell::new(None));
+    /// Whether every slot below `length` holds a value.
+    #[inline]
+    fn is_dense(&self) -> bool {
+        self.live.get() == self.length.get()
+    }
+}
 
-            // SAFETY: `data` does NOT need initialized due to it containing MaybeUninit and the
-            //         length being 0.
+impl<T> Segment<T> {
+    /// Drop every value in this segment, leaving it empty.
+    fn clear(&self) {
+        // If a destructor panics, the rest of the values leak rather than being dropped twice.
+        let length = self.length.replace(0);
+        let live = self.live.replace(0);
 
-            Box::from_raw(ptr)
+        unsafe {
+            if live == length {
+                std::ptr::drop_in_place(std::ptr::slice_from_raw_parts_mut(
+                    self.data.as_ptr(),
+                    length,
+                ));
+            } else {
+                for i in (0..length).filter(|&i| self.is_occupied(i)) {
+                    std::ptr::drop_in_place(self.ptr(i));
+                }
+            }
         }
     }
 }
 
-impl<T, const N: usize> Drop for Segment<T, N> {
+impl<T> Drop for Segment<T> {
     fn drop(&mut self) {
+        self.clear();
+
         unsafe {
-            for i in 0..self.length.get() {
-                self.data[i].get_mut().assume_init_drop();
+            let layout = Layout::array::<T>(self.capacity).unwrap();
+            if layout.size() != 0 {
+                std::alloc::dealloc(self.data.as_ptr() as *mut u8, layout);
             }
         }
     }
 }
 
-use std::cell::RefCell;
 /// A reference to a value within an [`Arena`]. It can be treated like any other reference type.
 ///
 /// But, it is exclusivly read only. However you can still use a [`Cell`] or [`RefCell`] for interior
@@ -59,33 +207,84 @@ use std::cell::RefCell;
 pub struct ArenaRef<T: Sized, const N: usize> {
     arena: Weak<ArenaInner<T, N>>,
 
-    // SAFETY: ptr MUST be contained within the tree of parent! And
-    //         it will be valid as long as arena is valid
-    ptr: *const T,
+    // Generation of the arena this was handed out in, see `Arena::reset`.
+    generation: u32,
+
+    // Stamp of the slot when this was handed out, see `Arena::take`. The value is looked up by
+    // its index, which also tells whether the stamp still matches.
+    stamp: u32,
+    index: u32,
 }
 
 impl<T, const N: usize> ArenaRef<T, N> {
+    fn new(inner: &Rc<ArenaInner<T, N>>, slot: Slot<T>) -> ArenaRef<T, N> {
+        ArenaRef {
+            arena: Rc::downgrade(inner),
+            generation: inner.generation.get(),
+            stamp: unsafe { &*slot.stamp }.get(),
+            index: ArenaIndex::<T>::from_usize(slot.index).into_raw(),
+        }
+    }
+
+    /// The value, if the arena is alive and its slot still has the same stamp.
+    fn get_ptr(&self) -> Option<*const T> {
+        if !ArenaInner::is_live(&self.arena, self.generation) {
+            return None;
+        }
+
+        let inner = unsafe { &*self.arena.as_ptr() };
+        let (segment, i) = inner.find(self.index as usize)?;
+        (segment.stamps[i].get() == self.stamp).then(|| segment.ptr(i) as *const T)
+    }
+
+    fn is_live(&self) -> bool {
+        self.get_ptr().is_some()
+    }
+
     ///  Try to retrieve the contained value.
     ///
-    ///  [`None`] corresponds to the parent [`Arena`] no longer existing
+    ///  [`None`] corresponds to the parent [`Arena`] no longer existing, having been
+    ///  [reset](Arena::reset) since this was handed out, or the value having been
+    ///  [removed](Arena::take).
     pub fn try_get(&self) -> Option<&T> {
-        // SAFETY: According to the 'weak_count' docs: `If no strong pointers remain, this will
-        //         return zero.` So this is a valid check for if the arena is still valid
-        if self.arena.weak_count() == 0 {
-            None
-        } else {
-            Some(unsafe { &*self.ptr })
-        }
+        self.get_ptr().map(|ptr| unsafe { &*ptr })
     }
 
     ///  Try to retrive the parent [`Arena`]. Returns [`None`] when it is no longer alive,
+    ///  or has been [reset](Arena::reset) since this was handed out.
     pub fn get_arena(&self) -> Option<Arena<T, N>> {
-        self.arena.upgrade().map(|inner| Arena { inner })
+        self.arena
+            .upgrade()
+            .filter(|inner| inner.generation.get() == self.generation)
+            .map(|inner| Arena { inner })
     }
 
     /// Test if two [`ArenaRef`]s are pointing to the same values in the same [`Arena`]s.
     pub fn ptr_eq(&self, other: &Self) -> bool {
-        self.ptr.eq(&other.ptr) && self.get_arena().eq(&other.get_arena())
+        self.index == other.index
+            && self.generation == other.generation
+            && self.stamp == other.stamp
+            && self.arena.ptr_eq(&other.arena)
+    }
+
+    /// Turn this into an [`ArenaRc`], which keeps the [`Arena`] alive.
+    ///
+    /// Returns [`None`] when the [`Arena`] is no longer alive, like [`Weak::upgrade`], or
+    /// whenever [`ArenaRef::try_get`] would.
+    pub fn upgrade(&self) -> Option<ArenaRc<T, N>> {
+        let ptr = self.get_ptr()?;
+
+        self.arena.upgrade().map(|arena| ArenaRc {
+            arena,
+            index: self.index,
+            ptr,
+        })
+    }
+
+    /// The [`ArenaIndex`] of this value, which can be turned back into a reference with
+    /// [`Arena::get`] or [`Arena::get_ref`].
+    pub fn index(&self) -> ArenaIndex<T> {
+        ArenaIndex::from_raw(self.index)
     }
 }
 
@@ -93,7 +292,9 @@ impl<T, const N: usize> Clone for ArenaRef<T, N> {
     fn clone(&self) -> Self {
         ArenaRef {
             arena: self.arena.clone(),
-            ptr: self.ptr,
+            generation: self.generation,
+            stamp: self.stamp,
+            index: self.index,
         }
     }
 }
@@ -125,229 +326,2965 @@ impl<T: Display, const N: usize> Display for ArenaRef<T, N> {
     }
 }
 
-struct ArenaInner<T, const N: usize> {
-    tail: Cell<*const Segment<T, N>>,
-    head: Box<Segment<T, N>>,
+impl<T, const N: usize> std::fmt::Pointer for ArenaRef<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let ptr = self.get_ptr().unwrap_or(std::ptr::null());
+        std::fmt::Pointer::fmt(&ptr, f)
+    }
 }
 
-impl<T, const N: usize> ArenaInner<T, N> {
-    fn alloc(&self, cont: T) -> *mut T {
-        let tail = unsafe { &*self.tail.get() };
+// Like `Rc`, comparisons look at the values. They panic if either arena is gone, just like
+// `Deref`. See `ByAddress` to compare the references themselves.
 
-        if tail.length.get() >= N {
-            let segment = Segment::new();
+impl<T: PartialEq, const N: usize> PartialEq for ArenaRef<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        **self == **other
+    }
+}
 
-            // This looks evil, but the box means this is valid
-            self.tail.set(&*segment as *const Segment<T, N>);
-            tail.next.set(Some(segment));
-        }
+impl<T: Eq, const N: usize> Eq for ArenaRef<T, N> {}
 
-        let tail = unsafe { &*self.tail.get() };
+impl<T: PartialOrd, const N: usize> PartialOrd for ArenaRef<T, N> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        (**self).partial_cmp(&**other)
+    }
+}
 
-        let old_length = tail.length.get();
-        let contents = unsafe {
-            let inner = &tail.data[old_length];
+impl<T: Ord, const N: usize> Ord for ArenaRef<T, N> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (**self).cmp(&**other)
+    }
+}
 
-            // SAFETY: since it has not been "allocated" in the arena,
-            //         it has not been shared, so it is free to write over.
+impl<T: Hash, const N: usize> Hash for ArenaRef<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (**self).hash(state);
+    }
+}
 
-            (&mut *inner.get()).write(cont)
-        };
+impl<T, const N: usize> std::borrow::Borrow<T> for ArenaRef<T, N> {
+    fn borrow(&self) -> &T {
+        self
+    }
+}
 
-        tail.length.set(old_length + 1);
-        contents
+impl<T, const N: usize> AsRef<T> for ArenaRef<T, N> {
+    fn as_ref(&self) -> &T {
+        self
     }
 }
 
-/// A typed memory arena that you can pass like an [`Rc`].
+/// Compares and hashes an [`ArenaRef`] by the slot it points to rather than by its value, see
+/// [`ArenaRef::ptr_eq`].
 ///
+/// Unlike comparing the values, this never panics, even once the arena is gone.
 ///
 /// Example:
 /// ```
 /// use light_rc_arena::*;
+/// use std::collections::HashSet;
 ///
 /// let arena = Arena::<i32>::new();
+/// let a = arena.alloc(1);
+/// let b = arena.alloc(1);
 ///
-/// // Still a reference to the same arena
-/// let arena_copy = arena.clone();
-///
-/// let x : ArenaRef<i32, _> = arena_copy.alloc(-1);
-/// dbg!(*x); // -1
+/// assert!(a == b);
+/// assert!(ByAddress(a.clone()) != ByAddress(b.clone()));
 ///
+/// let seen: HashSet<_> = [a.clone(), b, a].into_iter().map(ByAddress).collect();
+/// assert_eq!(seen.len(), 2);
 /// ```
-///
-pub struct Arena<T: Sized, const N: usize = 64> {
-    inner: Rc<ArenaInner<T, N>>,
+pub struct ByAddress<T, const N: usize>(pub ArenaRef<T, N>);
+
+impl<T, const N: usize> Clone for ByAddress<T, N> {
+    fn clone(&self) -> Self {
+        ByAddress(self.0.clone())
+    }
 }
 
-impl<T, const N: usize> Arena<T, N> {
-    /// Create a new Arena
-    pub fn new() -> Arena<T, N> {
-        assert!(N > 0, "Using zero for segment size is illegal!");
+impl<T, const N: usize> PartialEq for ByAddress<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.0.ptr_eq(&other.0)
+    }
+}
 
-        let new_segment = Segment::new();
-        let inner = Rc::new(ArenaInner {
-            // Temp value
-            tail: Cell::from(&*new_segment as *const Segment<T, N>),
-            head: new_segment,
-        });
+impl<T, const N: usize> Eq for ByAddress<T, N> {}
 
-        Arena { inner }
+impl<T, const N: usize> Hash for ByAddress<T, N> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.0.arena.as_ptr().hash(state);
+        self.0.index.hash(state);
     }
+}
 
-    /// Move an object into the arena, and return a [`ArenaRef`] to its new location.
-    #[inline]
-    pub fn alloc(&self, cont: T) -> ArenaRef<T, N> {
-        ArenaRef {
-            arena: Rc::downgrade(&self.inner),
-            ptr: self.inner.alloc(cont),
-        }
+impl<T: Debug, const N: usize> Debug for ByAddress<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ByAddress").field(&self.0).finish()
     }
+}
 
-    /// Create an iterator over every element in the [`Arena`], yeilding [`ArenaRef`]s
-    pub fn iter(&self) -> ArenaIterator<T, N> {
-        ArenaIterator {
-            arena_inner: self.inner.clone(),
-            pos: 0,
-            segment: self.inner.head.as_ref() as *const Segment<T, N>,
-        }
+// A handle hashed and compared by what it points to, so a set of them can be looked up by a plain
+// reference. Only kept by `HashConsArena` and `Interner`, which keep their arena alive.
+struct ByValue<R>(R);
+
+impl<T, const N: usize> std::borrow::Borrow<T> for ByValue<ArenaRef<T, N>> {
+    fn borrow(&self) -> &T {
+        &self.0
     }
 }
 
-impl<T, const N: usize> Clone for Arena<T, N> {
-    fn clone(&self) -> Self {
-        Arena {
-            inner: self.inner.clone(),
-        }
+impl<const N: usize> std::borrow::Borrow<str> for ByValue<ArenaStr<N>> {
+    fn borrow(&self) -> &str {
+        &self.0
     }
 }
 
-impl<T, const N: usize> PartialEq for Arena<T, N> {
+impl<R: Deref> PartialEq for ByValue<R>
+where
+    R::Target: PartialEq,
+{
     fn eq(&self, other: &Self) -> bool {
-        Rc::ptr_eq(&self.inner, &other.inner)
+        *self.0 == *other.0
     }
 }
 
+impl<R: Deref> Eq for ByValue<R> where R::Target: Eq {}
 
+impl<R: Deref> Hash for ByValue<R>
+where
+    R::Target: Hash,
+{
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (*self.0).hash(state);
+    }
+}
 
+/// A reference to a value within an [`Arena`] that keeps the whole [`Arena`] alive, the
+/// [`Rc`] to [`ArenaRef`]'s [`Weak`].
+///
+/// Unlike [`ArenaRef`] it can never dangle, at the cost of pinning every value in the
+/// [`Arena`] until the last [`ArenaRc`] is dropped.
+pub struct ArenaRc<T: Sized, const N: usize> {
+    arena: Rc<ArenaInner<T, N>>,
 
+    // The value can't be removed while the arena is shared.
+    index: u32,
 
-/// An iterator over every element in the [`Arena`], yeilding [`ArenaRef`]. 
-pub struct ArenaIterator<T: Sized, const N: usize> {
-    arena_inner: Rc<ArenaInner<T, N>>,
-    pos: usize,
-    segment: *const Segment<T, N>,
+    // SAFETY: ptr MUST be contained within the tree of arena.
+    ptr: *const T,
 }
-impl<T, const N: usize> Iterator for ArenaIterator<T, N> {
-    type Item = ArenaRef<T, N>;
-    fn next(&mut self) -> Option<Self::Item> {
-        unsafe {
-            if self.pos >= (*self.segment).length.get() {
-                self.pos = 0;
 
-                let old_segment = self.segment;
-                let next = (*self.segment).next.take()?;
-                self.segment = next.as_ref() as *const Segment<T, N>;
+impl<T, const N: usize> ArenaRc<T, N> {
+    /// Retrieve the parent [`Arena`].
+    pub fn get_arena(&self) -> Arena<T, N> {
+        Arena {
+            inner: self.arena.clone(),
+        }
+    }
 
-                (*old_segment).next.set(Some(next));
-            }
+    /// Turn this into an [`ArenaRef`], which does not keep the [`Arena`] alive.
+    pub fn downgrade(&self) -> ArenaRef<T, N> {
+        let slot = self.arena.locate(self.index as usize).unwrap();
+        ArenaRef::new(&self.arena, slot)
+    }
 
-            let maybe_uninit_ptr = (*self.segment).data[self.pos].get();
-            let ptr = (*maybe_uninit_ptr).as_ptr();
+    /// The [`ArenaIndex`] of this value.
+    pub fn index(&self) -> ArenaIndex<T> {
+        ArenaIndex::from_raw(self.index)
+    }
 
-            self.pos += 1;
+    /// Test if two [`ArenaRc`]s are pointing to the same values in the same [`Arena`]s.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.ptr.eq(&other.ptr) && Rc::ptr_eq(&self.arena, &other.arena)
+    }
+}
 
-            Some(ArenaRef {
-                arena: Rc::downgrade(&self.arena_inner),
-                ptr: ptr,
-            })
+impl<T, const N: usize> Clone for ArenaRc<T, N> {
+    fn clone(&self) -> Self {
+        ArenaRc {
+            arena: self.arena.clone(),
+            index: self.index,
+            ptr: self.ptr,
         }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::cell::Cell;
+impl<T, const N: usize> Deref for ArenaRc<T, N> {
+    type Target = T;
 
-    #[test]
-    fn basic_usage() {
-        let arena: Arena<Cell<u32>, 8> = Arena::new();
-        for i in 0..128 {
-            let r = arena.alloc(Cell::new(i));
-            // Test value
-            assert_eq!(r.get(), i);
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: we hold a strong reference, so the arena is alive.
+        unsafe { &*self.ptr }
+    }
+}
 
-            // Test mutibility
-            assert_eq!(r.replace(1), i);
+impl<T: Debug, const N: usize> Debug for ArenaRc<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ArenaRc").field(&**self).finish()
+    }
+}
 
-            // Ensure set
-            assert_eq!(r.get(), 1);
-        }
+impl<T: Display, const N: usize> Display for ArenaRc<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(&**self, f)
+    }
+}
 
-        let r = arena.alloc(Cell::new(1));
-        assert_eq!(r.get(), 1);
+/// A slot set aside in an [`Arena`] by [`Arena::alloc_uninit`], for a value that is only written
+/// later with [`ArenaUninit::init`].
+///
+/// Until then, iterating over the [`Arena`] skips the slot, and the [`ArenaRef`]s from
+/// [`ArenaUninit::arena_ref`] can't be read. Iterators created before the value was written keep
+/// skipping it. Dropping it without calling `init` gives the slot
+/// back to the [`Arena`].
+pub struct ArenaUninit<T: Sized, const N: usize> {
+    arena: Weak<ArenaInner<T, N>>,
+    generation: u32,
 
-        drop(arena);
+    // Stamp of the slot while it is set aside, it is bumped once the value is written.
+    stamp: u32,
 
-        assert_eq!(r.try_get(), None);
+    // SAFETY: the segment and slot are only valid while the generation is current.
+    segment: NonNull<Segment<T>>,
+    slot: Slot<T>,
+}
+
+impl<T, const N: usize> ArenaUninit<T, N> {
+    /// An [`ArenaRef`] to the value this slot will hold, which can be stored away right now.
+    ///
+    /// It becomes readable once [`ArenaUninit::init`] is called, until then
+    /// [`ArenaRef::try_get`] returns [`None`].
+    pub fn arena_ref(&self) -> ArenaRef<T, N> {
+        ArenaRef {
+            arena: self.arena.clone(),
+            generation: self.generation,
+            stamp: self.stamp + 1,
+            index: ArenaIndex::<T>::from_usize(self.slot.index).into_raw(),
+        }
     }
 
-    #[test]
-    fn usage_guide_from_readme() {
-        // Making an arena
-        type MyType = Cell<i32>;
-        let arena: Arena<MyType> = Arena::new();
+    /// Move the value into the slot, and return an [`ArenaRef`] to it.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the [`Arena`] no longer exists, or has been [reset](Arena::reset) since the
+    /// slot was set aside. See [`ArenaUninit::try_init`] for a version that does not.
+    pub fn init(self, value: T) -> ArenaRef<T, N> {
+        match self.try_init(value) {
+            Ok(r) => r,
+            Err(_) => panic!("The arena assosiated with this value is no longer valid!"),
+        }
+    }
 
-        // Allocating objects:
-        //
-        // These can be treated as &'arena MyType objects. You should wrap
-        // them in a Cell or RefCell to mutate.
+    /// Like [`ArenaUninit::init`], but hands the value back when the [`Arena`] no longer exists,
+    /// or has been [reset](Arena::reset) since the slot was set aside.
+    pub fn try_init(self, value: T) -> Result<ArenaRef<T, N>, T> {
+        if !ArenaInner::is_live(&self.arena, self.generation) {
+            return Err(value);
+        }
 
-        let obj1: ArenaRef<MyType, _> = arena.alloc(Cell::new(0));
-        let obj2: ArenaRef<MyType, _> = arena.alloc(Cell::new(1));
-        let obj3: ArenaRef<MyType, _> = arena.alloc(Cell::new(2));
+        let inner = unsafe { &*self.arena.as_ptr() };
+        let segment = unsafe { self.segment.as_ref() };
 
-        assert_eq!(obj1.get(), 0); //> 0
-        assert_eq!(obj2.get(), 1); //> 1
+        // SAFETY: the slot is vacant and on no free list, so nothing else can see it.
+        unsafe { self.slot.ptr.write(value) };
 
-        obj3.set(-99);
-        assert_eq!(obj3.get(), -99); //> -99
+        unsafe { &*self.slot.stamp }.set(self.stamp + 1);
+        segment.live.set(segment.live.get() + 1);
+        inner.len.set(inner.len.get() + 1);
 
-        // You can clone the Arena or ArenaRefs as much as you like (just like an Rc)
-        let arena2 = arena.clone();
-        let _obj1 = obj1.clone();
+        // Iterators already going didn't count the value, so they must not visit it either. Only
+        // slots set aside before one of them was created are within reach.
+        if inner.iterators.get() != 0 && self.slot.index < inner.iteration_end.get() {
+            let mut late = inner.late.borrow_mut();
+            let order = late.len();
+            late.insert(self.slot.index, order);
+            segment.late.set(segment.late.get() + 1);
+        }
 
-        // And you can even get an Arena from an ArenaRef (though this is not recommended).
-        let arena3: Option<Arena<MyType>> = obj1.get_arena();
+        // The slot now holds a value, so dropping `self` leaves it alone.
+        Ok(self.arena_ref())
+    }
+}
 
-        // But fair warning! An ArenaRef will NOT keep the arena alive!
+impl<T, const N: usize> Drop for ArenaUninit<T, N> {
+    fn drop(&mut self) {
+        if !ArenaInner::is_live(&self.arena, self.generation) {
+            return;
+        }
 
-        // And it is YOUR RESPONSIBILITY to keep the arena alive
-        drop(arena);
-        drop(arena2);
-        drop(arena3);
+        if unsafe { &*self.slot.stamp }.get() == self.stamp {
+            let inner = unsafe { &*self.arena.as_ptr() };
+            let i = self.slot.index - unsafe { self.segment.as_ref() }.base.get();
+            inner.free.borrow_mut().push((self.segment, i));
+        }
+    }
+}
 
-        // let value = &*obj1; // PANICS
+/// A reference to a contiguous run of values within an [`Arena`], the `[T]` version of an
+/// [`ArenaRef`].
+///
+/// Like an [`ArenaRef`], it does NOT keep the [`Arena`] alive.
+pub struct ArenaSlice<T: Sized, const N: usize> {
+    arena: Weak<ArenaInner<T, N>>,
+    generation: u32,
 
-        // You can test if your arena is still valid:
-        let value: Option<&MyType> = obj1.try_get();
-        assert_eq!(value, None)
-    }
+    // Index of the first slot, the rest follow it. Their stamps are all `PINNED`.
+    index: usize,
 
+    // SAFETY: ptr..ptr + len MUST be contained within a single segment of parent.
+    ptr: *const T,
+    len: usize,
+}
 
-    #[test]
-    fn test_iter(){
-        let arena: Arena<i32, 32> = Arena::new();
-        for i in 0..1024 {
-            arena.alloc(i);
+impl<T, const N: usize> ArenaSlice<T, N> {
+    fn new(inner: &Rc<ArenaInner<T, N>>, first: Slot<T>, len: usize) -> Self {
+        ArenaSlice {
+            arena: Rc::downgrade(inner),
+            generation: inner.generation.get(),
+            index: first.index,
+            ptr: first.ptr,
+            len,
         }
+    }
 
-        let lhs : Vec<i32> = arena.iter().map(|x| *x).collect();
-        let rhs : Vec<i32> = (0..1024).collect();
-        assert_eq!(lhs, rhs);
+    ///  Try to retrieve the contained values.
+    ///
+    ///  [`None`] corresponds to the parent [`Arena`] no longer existing, or having been
+    ///  [reset](Arena::reset) since this was handed out.
+    pub fn try_get(&self) -> Option<&[T]> {
+        if ArenaInner::is_live(&self.arena, self.generation) {
+            Some(unsafe { std::slice::from_raw_parts(self.ptr, self.len) })
+        } else {
+            None
+        }
+    }
+
+    ///  Try to retrive the parent [`Arena`]. Returns [`None`] when it is no longer alive,
+    ///  or has been [reset](Arena::reset) since this was handed out.
+    pub fn get_arena(&self) -> Option<Arena<T, N>> {
+        self.arena
+            .upgrade()
+            .filter(|inner| inner.generation.get() == self.generation)
+            .map(|inner| Arena { inner })
+    }
+
+    /// Get an [`ArenaRef`] to a single value of the run.
+    pub fn get_ref(&self, index: usize) -> Option<ArenaRef<T, N>> {
+        (index < self.len).then(|| ArenaRef {
+            arena: self.arena.clone(),
+            generation: self.generation,
+            stamp: PINNED,
+            index: ArenaIndex::<T>::from_usize(self.index + index).into_raw(),
+        })
+    }
+
+    /// Test if two [`ArenaSlice`]s are pointing to the same run in the same [`Arena`]s.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.ptr.eq(&other.ptr)
+            && self.len == other.len
+            && self.generation == other.generation
+            && self.arena.ptr_eq(&other.arena)
+    }
+}
+
+impl<T, const N: usize> Clone for ArenaSlice<T, N> {
+    fn clone(&self) -> Self {
+        ArenaSlice {
+            arena: self.arena.clone(),
+            generation: self.generation,
+            index: self.index,
+            ptr: self.ptr,
+            len: self.len,
+        }
+    }
+}
+
+impl<T, const N: usize> Deref for ArenaSlice<T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &Self::Target {
+        self.try_get()
+            .expect("The arena assosiated with this value is no longer valid!")
+    }
+}
+
+impl<T: Debug, const N: usize> Debug for ArenaSlice<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.try_get() {
+            Some(values) => f.debug_tuple("ArenaSlice").field(&values).finish(),
+            None => f.debug_tuple("ArenaSlice").field(&"<dead arena>").finish(),
+        }
+    }
+}
+
+/// A plain index to a value within an [`Arena`], an alternative to [`ArenaRef`] that takes 4
+/// bytes and has no pointer in it.
+///
+/// It is the position of the value's slot, counting every slot of every segment before it
+/// (`segment number * N + slot` unless slices opened bigger segments). So an arena filled
+/// the same way always hands out the same indexes, and they can be written to disk.
+///
+/// An [`ArenaIndex`] is not tied to an arena, and has to be resolved with [`Arena::get`] or
+/// [`Arena::get_ref`].
+pub struct ArenaIndex<T> {
+    index: u32,
+    _marker: PhantomData<fn() -> T>,
+}
+
+impl<T> ArenaIndex<T> {
+    /// Create an index from its raw value.
+    pub const fn from_raw(index: u32) -> ArenaIndex<T> {
+        ArenaIndex {
+            index,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Get the raw value of this index.
+    pub const fn into_raw(self) -> u32 {
+        self.index
+    }
+
+    fn from_usize(index: usize) -> ArenaIndex<T> {
+        let index = u32::try_from(index).expect("ArenaIndex can only address u32::MAX slots!");
+        Self::from_raw(index)
+    }
+}
+
+impl<T> Clone for ArenaIndex<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T> Copy for ArenaIndex<T> {}
+
+impl<T> PartialEq for ArenaIndex<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.index == other.index
+    }
+}
+
+impl<T> Eq for ArenaIndex<T> {}
+
+impl<T> PartialOrd for ArenaIndex<T> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<T> Ord for ArenaIndex<T> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.index.cmp(&other.index)
+    }
+}
+
+impl<T> Hash for ArenaIndex<T> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.index.hash(state);
+    }
+}
+
+impl<T> Debug for ArenaIndex<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ArenaIndex").field(&self.index).finish()
+    }
+}
+
+/// Why an allocation failed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocErrorKind {
+    /// A new segment would go past the limit set with [`Arena::set_byte_limit`] or
+    /// [`Arena::set_segment_limit`].
+    LimitReached,
+
+    /// The global allocator could not provide a new segment.
+    OutOfMemory,
+}
+
+/// The error returned by [`Arena::try_alloc`]. It hands back the value that could not be
+/// allocated.
+pub struct AllocError<T> {
+    value: T,
+    kind: AllocErrorKind,
+}
+
+impl<T> AllocError<T> {
+    /// Why the allocation failed.
+    pub fn kind(&self) -> AllocErrorKind {
+        self.kind
+    }
+
+    /// Take back the value that could not be allocated.
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Debug for AllocError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AllocError")
+            .field("kind", &self.kind)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<T> Display for AllocError<T> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.kind {
+            AllocErrorKind::LimitReached => write!(f, "the arena has reached its allocation limit"),
+            AllocErrorKind::OutOfMemory => write!(f, "out of memory while growing the arena"),
+        }
+    }
+}
+
+impl<T> std::error::Error for AllocError<T> {}
+
+struct ArenaInner<T, const N: usize> {
+    // Every segment in order, owned by the arena. The ones after the tail are empty spares.
+    segments: RefCell<Vec<NonNull<Segment<T>>>>,
+
+    tail: Cell<NonNull<Segment<T>>>,
+    tail_index: Cell<usize>,
+
+    len: Cell<usize>,
+    capacity: Cell<usize>,
+    segment_count: Cell<usize>,
+    allocated_bytes: Cell<usize>,
+
+    segment_limit: Cell<Option<usize>>,
+    byte_limit: Cell<Option<usize>>,
+
+    // Bumped on every reset. Handles remember the generation they were handed out in, and go
+    // stale once it moves on, as their slot may hold a newer value by then.
+    generation: Cell<u32>,
+
+    // Slots whose values were removed, handed out again by `alloc`.
+    free: RefCell<Vec<(NonNull<Segment<T>>, usize)>>,
+
+    // Live `ArenaIterator`s and `Iter`s. Filling a hole behind their back would have them visit
+    // a value allocated after they were created, so the free list is left alone until they are
+    // dropped.
+    iterators: Cell<usize>,
+
+    // One past the last slot any of them may reach. Slots set aside before that by
+    // `alloc_uninit` and filled while `iterators` isn't zero are listed in `late`, by their index
+    // along with how many were listed before. Cursors created before a slot was listed skip it.
+    // Both are cleared with `iterators`.
+    iteration_end: Cell<usize>,
+    late: RefCell<HashMap<usize, usize>>,
+}
+
+impl<T, const N: usize> Drop for ArenaInner<T, N> {
+    fn drop(&mut self) {
+        for segment in self.segments.get_mut().drain(..) {
+            drop(unsafe { Box::from_raw(segment.as_ptr()) });
+        }
+    }
+}
+
+impl<T, const N: usize> ArenaInner<T, N> {
+    fn with_capacity(capacity: usize) -> ArenaInner<T, N> {
+        let segment = NonNull::from(Box::leak(Segment::new(capacity, 0)));
+        Self::from_segments(vec![segment])
+    }
+
+    /// Build an arena out of empty segments, filling them from the first.
+    fn from_segments(segments: Vec<NonNull<Segment<T>>>) -> ArenaInner<T, N> {
+        let inner = ArenaInner {
+            tail: Cell::new(segments[0]),
+            tail_index: Cell::new(0),
+
+            len: Cell::new(0),
+            capacity: Cell::new(0),
+            segment_count: Cell::new(0),
+            allocated_bytes: Cell::new(0),
+
+            segments: RefCell::new(segments),
+
+            segment_limit: Cell::new(None),
+            byte_limit: Cell::new(None),
+
+            generation: Cell::new(0),
+
+            free: RefCell::new(Vec::new()),
+            iterators: Cell::new(0),
+            iteration_end: Cell::new(0),
+            late: RefCell::new(HashMap::new()),
+        };
+
+        drop(inner.rewind(usize::MAX));
+        inner
+    }
+
+    /// Go back to filling from the first segment, keeping only the first `keep` (at least one).
+    ///
+    /// The values are left in place, and the dropped segments are handed back to be freed.
+    fn rewind(&self, keep: usize) -> Vec<NonNull<Segment<T>>> {
+        let mut segments = self.segments.borrow_mut();
+        let keep = keep.clamp(1, segments.len());
+        let freed = segments.split_off(keep);
+
+        let sizes = segments
+            .iter()
+            .map(|segment| unsafe { segment.as_ref() }.capacity);
+
+        self.tail.set(segments[0]);
+        self.tail_index.set(0);
+        self.free.borrow_mut().clear();
+
+        self.len.set(0);
+        self.capacity.set(sizes.clone().sum());
+        self.segment_count.set(segments.len());
+        self.allocated_bytes.set(
+            sizes
+                .map(|size| Segment::<T>::size_for(size).unwrap())
+                .sum(),
+        );
+
+        freed
+    }
+
+    /// A cursor for a new `ArenaIterator` or `Iter`, which calls `end_iteration` once dropped.
+    fn start_iteration(&self) -> Cursor<T> {
+        let tail = unsafe { self.tail.get().as_ref() };
+        let end = tail.base.get() + tail.length.get();
+
+        self.iterators.set(self.iterators.get() + 1);
+        self.iteration_end.set(self.iteration_end.get().max(end));
+        Cursor::new(self)
+    }
+
+    /// Called by `ArenaIterator`s and `Iter`s once they are dropped.
+    fn end_iteration(&self) {
+        self.iterators.set(self.iterators.get() - 1);
+
+        if self.iterators.get() == 0 {
+            for (index, _) in self.late.borrow_mut().drain() {
+                if let Some((segment, _)) = self.find(index) {
+                    segment.late.set(0);
+                }
+            }
+            self.iteration_end.set(0);
+        }
+    }
+
+    /// Whether a handle to this arena, handed out in `generation`, may still be read.
+    #[inline]
+    fn is_live(arena: &Weak<Self>, generation: u32) -> bool {
+        // SAFETY: According to the 'weak_count' docs: `If no strong pointers remain, this will
+        //         return zero.` So the allocation is still valid whenever it isn't zero.
+        arena.weak_count() != 0 && unsafe { &*arena.as_ptr() }.generation.get() == generation
+    }
+
+    /// The `i`th segment of the arena.
+    #[inline]
+    fn segment(&self, i: usize) -> Option<&Segment<T>> {
+        // SAFETY: segments live as long as the arena.
+        let segment = *self.segments.borrow().get(i)?;
+        Some(unsafe { &*segment.as_ptr() })
+    }
+
+    /// Find the segment and slot within it for `index`, if that slot has been used.
+    fn find(&self, index: usize) -> Option<(&Segment<T>, usize)> {
+        let segments = self.segments.borrow();
+
+        let i = segments
+            .partition_point(|segment| unsafe { segment.as_ref() }.base.get() <= index)
+            .checked_sub(1)?;
+        let segment = unsafe { &*segments[i].as_ptr() };
+
+        let slot = index - segment.base.get();
+        (slot < segment.length.get()).then_some((segment, slot))
+    }
+
+    /// Find the value at `index`, if that slot holds one.
+    fn locate(&self, index: usize) -> Option<Slot<T>> {
+        let (segment, i) = self.find(index)?;
+        segment.is_occupied(i).then(|| segment.slot(i))
+    }
+
+    /// Allocate a new segment, unless it would go past the limits of this arena.
+    fn new_segment(
+        &self,
+        capacity: usize,
+        base: usize,
+    ) -> Result<NonNull<Segment<T>>, AllocErrorKind> {
+        let bytes = Segment::<T>::size_for(capacity).ok_or(AllocErrorKind::OutOfMemory)?;
+
+        let over_segments = self
+            .segment_limit
+            .get()
+            .is_some_and(|limit| self.segment_count.get() >= limit);
+        let over_bytes = self
+            .byte_limit
+            .get()
+            .is_some_and(|limit| self.allocated_bytes.get().saturating_add(bytes) > limit);
+
+        if over_segments || over_bytes {
+            return Err(AllocErrorKind::LimitReached);
+        }
+
+        let segment = Segment::try_new(capacity, base).ok_or(AllocErrorKind::OutOfMemory)?;
+
+        self.capacity.set(self.capacity.get() + capacity);
+        self.segment_count.set(self.segment_count.get() + 1);
+        self.allocated_bytes.set(self.allocated_bytes.get() + bytes);
+        Ok(NonNull::from(Box::leak(segment)))
+    }
+
+    /// Get a segment with at least `len` free slots at its end. Moves on to the next reserved
+    /// segment if the tail is too full, or opens a new one when there is none big enough.
+    fn try_tail_with_room(&self, len: usize) -> Result<&Segment<T>, AllocErrorKind> {
+        let tail = unsafe { &*self.tail.get().as_ptr() };
+
+        if tail.remaining() >= len {
+            return Ok(tail);
+        }
+
+        let next = self.tail_index.get() + 1;
+        let mut segments = self.segments.borrow_mut();
+
+        // Segments after the tail are spares from `reserve`, and are still empty.
+        let segment = match segments.get(next) {
+            Some(&spare) if unsafe { spare.as_ref() }.capacity >= len => spare,
+            _ => {
+                let segment = self.new_segment(len.max(N), tail.end())?;
+                segments.insert(next, segment);
+
+                // Make room for it by moving the spares along. They are empty, so no index
+                // handed out changes.
+                let mut base = unsafe { segment.as_ref() }.end();
+                for spare in &segments[next + 1..] {
+                    let spare = unsafe { spare.as_ref() };
+                    spare.base.set(base);
+                    base = spare.end();
+                }
+
+                segment
+            }
+        };
+
+        self.tail.set(segment);
+        self.tail_index.set(next);
+
+        Ok(unsafe { &*segment.as_ptr() })
+    }
+
+    /// Make sure `additional` more values can be allocated one by one without opening a
+    /// segment, by adding spare segments after the tail.
+    fn try_reserve(&self, additional: usize) -> Result<(), AllocErrorKind> {
+        let mut segments = self.segments.borrow_mut();
+
+        let available: usize = segments[self.tail_index.get()..]
+            .iter()
+            .map(|segment| unsafe { segment.as_ref() }.remaining())
+            .sum();
+
+        if available >= additional {
+            return Ok(());
+        }
+
+        let base = unsafe { segments.last().unwrap().as_ref() }.end();
+        let capacity = (additional - available)
+            .checked_next_multiple_of(N)
+            .ok_or(AllocErrorKind::OutOfMemory)?;
+        let segment = self.new_segment(capacity, base)?;
+        segments.push(segment);
+
+        Ok(())
+    }
+
+    /// Like [`ArenaInner::try_tail_with_room`], but panics when the arena's limits are reached,
+    /// and aborts when out of memory.
+    fn tail_with_room(&self, len: usize) -> &Segment<T> {
+        match self.try_tail_with_room(len) {
+            Ok(tail) => tail,
+            Err(AllocErrorKind::LimitReached) => {
+                panic!("The arena has reached its allocation limit!")
+            }
+            Err(AllocErrorKind::OutOfMemory) => match Layout::array::<T>(len.max(N)) {
+                Ok(layout) => std::alloc::handle_alloc_error(layout),
+                Err(_) => panic!("Segment is too large!"),
+            },
+        }
+    }
+
+    fn alloc(&self, cont: T) -> Slot<T> {
+        self.alloc_free(cont)
+            .unwrap_or_else(|cont| self.alloc_in(self.tail_with_room(1), cont))
+    }
+
+    fn try_alloc(&self, cont: T) -> Result<Slot<T>, AllocError<T>> {
+        let cont = match self.alloc_free(cont) {
+            Ok(slot) => return Ok(slot),
+            Err(cont) => cont,
+        };
+
+        match self.try_tail_with_room(1) {
+            Ok(tail) => Ok(self.alloc_in(tail, cont)),
+            Err(kind) => Err(AllocError { value: cont, kind }),
+        }
+    }
+
+    /// Move a value into a slot from the free list, or hand it back if there is none to use.
+    fn alloc_free(&self, cont: T) -> Result<Slot<T>, T> {
+        if self.iterators.get() != 0 {
+            return Err(cont);
+        }
+
+        let Some((segment, i)) = self.free.borrow_mut().pop() else {
+            return Err(cont);
+        };
+        let segment = unsafe { &*segment.as_ptr() };
+
+        // SAFETY: the slot is vacant, so nothing else can see it.
+        let slot = segment.slot(i);
+        unsafe { slot.ptr.write(cont) };
+
+        segment.stamps[i].set(segment.stamps[i].get() + 1);
+        segment.live.set(segment.live.get() + 1);
+        self.len.set(self.len.get() + 1);
+        Ok(slot)
+    }
+
+    /// Set a slot aside for a value written later, see [`ArenaUninit`].
+    ///
+    /// The slot is left vacant, so it is skipped like a hole, but it is on no free list either.
+    fn reserve_slot(&self) -> (NonNull<Segment<T>>, usize) {
+        let free = if self.iterators.get() == 0 {
+            self.free.borrow_mut().pop()
+        } else {
+            None
+        };
+
+        if let Some(free) = free {
+            return free;
+        }
+
+        let tail = self.tail_with_room(1);
+        let i = tail.length.get();
+
+        tail.stamps[i].set(0);
+        tail.length.set(i + 1);
+        (NonNull::from(tail), i)
+    }
+
+    fn alloc_in(&self, tail: &Segment<T>, cont: T) -> Slot<T> {
+        let old_length = tail.length.get();
+        let slot = tail.slot(old_length);
+
+        // SAFETY: since it has not been "allocated" in the arena,
+        //         it has not been shared, so it is free to write over.
+        unsafe { slot.ptr.write(cont) };
+
+        // Slots past `length` haven't been used since the last reset, so no handle can have seen
+        // this stamp yet.
+        tail.stamps[old_length].set(1);
+        tail.length.set(old_length + 1);
+        tail.live.set(tail.live.get() + 1);
+        self.len.set(self.len.get() + 1);
+        slot
+    }
+
+    /// Bitwise copy `len` values from `src` into one contiguous run, returning its start.
+    ///
+    /// SAFETY: `src` must be valid for `len` reads, and the caller must make sure the values are
+    ///         not used or dropped at the source afterwards (unless they are `Copy`).
+    unsafe fn alloc_copy(&self, src: *const T, len: usize) -> Slot<T> {
+        let tail = self.tail_with_room(len);
+
+        let old_length = tail.length.get();
+        let first = tail.slot(old_length);
+
+        // No user code runs between finding room and bumping the length, so the run can't be
+        // split by a reentrant `alloc`.
+        unsafe { std::ptr::copy_nonoverlapping(src, first.ptr, len) };
+
+        for stamp in &tail.stamps[old_length..old_length + len] {
+            stamp.set(PINNED);
+        }
+        tail.length.set(old_length + len);
+        tail.live.set(tail.live.get() + len);
+        self.len.set(self.len.get() + len);
+        first
+    }
+
+    fn alloc_vec(&self, mut values: Vec<T>) -> Slot<T> {
+        unsafe {
+            let contents = self.alloc_copy(values.as_ptr(), values.len());
+
+            // The values have been moved into the arena.
+            values.set_len(0);
+            contents
+        }
+    }
+
+    /// Move values into the arena a segment at a time, filling up the tail before opening new
+    /// ones. Unlike `alloc_vec`, the values may be split over several segments.
+    fn alloc_spread(&self, values: Vec<T>, mut each: impl FnMut(Slot<T>)) {
+        let mut values = values.into_iter();
+
+        while values.len() != 0 {
+            let tail = self.tail_with_room(1);
+
+            let old_length = tail.length.get();
+            let len = tail.remaining().min(values.len());
+
+            // SAFETY: the copied values are forgotten at the source right after, and no user code
+            //         runs in between.
+            unsafe {
+                std::ptr::copy_nonoverlapping(values.as_slice().as_ptr(), tail.ptr(old_length), len)
+            };
+            values.by_ref().take(len).for_each(std::mem::forget);
+
+            for stamp in &tail.stamps[old_length..old_length + len] {
+                stamp.set(1);
+            }
+            tail.length.set(old_length + len);
+            tail.live.set(tail.live.get() + len);
+            self.len.set(self.len.get() + len);
+
+            for i in old_length..old_length + len {
+                each(tail.slot(i));
+            }
+        }
+    }
+
+    /// Move the value out of a slot, leaving it vacant for `alloc` to reuse.
+    ///
+    /// SAFETY: the slot must hold a value that is not `PINNED`, and it must not be borrowed.
+    unsafe fn take(&self, segment: &Segment<T>, i: usize) -> T {
+        let value = unsafe { segment.ptr(i).read() };
+
+        let stamp = segment.stamps[i].get() + 1;
+        segment.stamps[i].set(stamp);
+        segment.live.set(segment.live.get() - 1);
+        self.len.set(self.len.get() - 1);
+
+        if stamp != RETIRED {
+            self.free.borrow_mut().push((NonNull::from(segment), i));
+        }
+
+        value
+    }
+}
+
+/// A snapshot of how much an [`Arena`] holds, see [`Arena::stats`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArenaStats {
+    /// Number of values in the arena.
+    pub len: usize,
+
+    /// Number of slots in every segment, used or not.
+    pub capacity: usize,
+
+    /// Number of segments.
+    pub segment_count: usize,
+
+    /// Bytes held by the segments, including their headers.
+    pub allocated_bytes: usize,
+}
+
+/// A typed memory arena that you can pass like an [`Rc`].
+///
+///
+/// Example:
+/// ```
+/// use light_rc_arena::*;
+///
+/// let arena = Arena::<i32>::new();
+///
+/// // Still a reference to the same arena
+/// let arena_copy = arena.clone();
+///
+/// let x : ArenaRef<i32, _> = arena_copy.alloc(-1);
+/// dbg!(*x); // -1
+///
+/// ```
+///
+pub struct Arena<T: Sized, const N: usize = 64> {
+    inner: Rc<ArenaInner<T, N>>,
+}
+
+impl<T, const N: usize> Arena<T, N> {
+    /// Create a new Arena
+    pub fn new() -> Arena<T, N> {
+        Self::with_capacity(N)
+    }
+
+    /// Create a new Arena with room for at least `capacity` values before it has to allocate
+    /// again.
+    ///
+    /// The first segment is rounded up to a multiple of `N`.
+    pub fn with_capacity(capacity: usize) -> Arena<T, N> {
+        assert!(N > 0, "Using zero for segment size is illegal!");
+
+        let capacity = capacity
+            .max(1)
+            .checked_next_multiple_of(N)
+            .expect("Segment is too large!");
+
+        Arena {
+            inner: Rc::new(ArenaInner::with_capacity(capacity)),
+        }
+    }
+
+    /// Like [`Arena::from_iter`](FromIterator::from_iter), but also returns an [`ArenaRef`] to
+    /// every value, in order.
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let (arena, refs) = Arena::<char>::from_iter_with_refs("abc".chars());
+    ///
+    /// assert_eq!(*refs[1], 'b');
+    /// assert_eq!(arena.len(), 3);
+    /// ```
+    pub fn from_iter_with_refs<I: IntoIterator<Item = T>>(
+        iter: I,
+    ) -> (Arena<T, N>, Vec<ArenaRef<T, N>>) {
+        let values: Vec<T> = iter.into_iter().collect();
+
+        let arena = Arena::with_capacity(values.len());
+        let mut refs = Vec::with_capacity(values.len());
+        arena
+            .inner
+            .alloc_spread(values, |slot| refs.push(ArenaRef::new(&arena.inner, slot)));
+
+        (arena, refs)
+    }
+
+    /// Move an object into the arena, and return a [`ArenaRef`] to its new location.
+    #[inline]
+    pub fn alloc(&self, cont: T) -> ArenaRef<T, N> {
+        ArenaRef::new(&self.inner, self.inner.alloc(cont))
+    }
+
+    /// Move an object into the arena, and return an [`ArenaRc`] to its new location.
+    #[inline]
+    pub fn alloc_rc(&self, cont: T) -> ArenaRc<T, N> {
+        let slot = self.inner.alloc(cont);
+        ArenaRc {
+            arena: self.inner.clone(),
+            index: ArenaIndex::<T>::from_usize(slot.index).into_raw(),
+            ptr: slot.ptr,
+        }
+    }
+
+    /// Like [`Arena::alloc`], but hands the value back in an [`AllocError`] instead of
+    /// panicking or aborting when the arena can't grow.
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let arena = Arena::<i32, 2>::new();
+    /// arena.set_segment_limit(Some(1));
+    ///
+    /// assert!(arena.try_alloc(1).is_ok());
+    /// assert!(arena.try_alloc(2).is_ok());
+    ///
+    /// let err = arena.try_alloc(3).unwrap_err();
+    /// assert_eq!(err.kind(), AllocErrorKind::LimitReached);
+    /// assert_eq!(err.into_inner(), 3);
+    /// ```
+    #[inline]
+    pub fn try_alloc(&self, cont: T) -> Result<ArenaRef<T, N>, AllocError<T>> {
+        let slot = self.inner.try_alloc(cont)?;
+        Ok(ArenaRef::new(&self.inner, slot))
+    }
+
+    /// Set a slot aside for a value that is written later, with [`ArenaUninit::init`]. Values
+    /// can hold [`ArenaRef`]s to it in the meantime, see [`ArenaUninit::arena_ref`].
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    /// use std::cell::RefCell;
+    ///
+    /// struct Node {
+    ///     parent: Option<ArenaRef<Node, 64>>,
+    ///     children: RefCell<Vec<ArenaRef<Node, 64>>>,
+    /// }
+    ///
+    /// let arena = Arena::<Node>::new();
+    ///
+    /// let root = arena.alloc_uninit();
+    /// let child = arena.alloc(Node {
+    ///     parent: Some(root.arena_ref()),
+    ///     children: RefCell::default(),
+    /// });
+    /// let root = root.init(Node {
+    ///     parent: None,
+    ///     children: RefCell::new(vec![child.clone()]),
+    /// });
+    ///
+    /// assert!(child.parent.as_ref().unwrap().ptr_eq(&root));
+    /// ```
+    pub fn alloc_uninit(&self) -> ArenaUninit<T, N> {
+        let (segment, i) = self.inner.reserve_slot();
+        let slot = unsafe { segment.as_ref() }.slot(i);
+
+        ArenaUninit {
+            arena: Rc::downgrade(&self.inner),
+            generation: self.inner.generation.get(),
+            stamp: unsafe { &*slot.stamp }.get(),
+            segment,
+            slot,
+        }
+    }
+
+    /// Build a value that holds an [`ArenaRef`] to itself, like [`Rc::new_cyclic`].
+    ///
+    /// The [`ArenaRef`] passed to `f` can be stored, but not read until this returns. If `f`
+    /// panics, the slot is given back to the arena.
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// struct Loop {
+    ///     next: ArenaRef<Loop, 64>,
+    /// }
+    ///
+    /// let arena = Arena::<Loop>::new();
+    /// let node = arena.alloc_cyclic(|me| Loop { next: me.clone() });
+    ///
+    /// assert!(node.next.next.ptr_eq(&node));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics if `f` resets the arena.
+    pub fn alloc_cyclic<F: FnOnce(&ArenaRef<T, N>) -> T>(&self, f: F) -> ArenaRef<T, N> {
+        let uninit = self.alloc_uninit();
+        let value = f(&uninit.arena_ref());
+        uninit.init(value)
+    }
+
+    /// Limit the total bytes held by the segments of this arena, counting segments that already
+    /// exist. [`None`] removes the limit.
+    ///
+    /// Once reached, [`Arena::try_alloc`] fails and the other allocation methods panic.
+    pub fn set_byte_limit(&self, limit: Option<usize>) {
+        self.inner.byte_limit.set(limit);
+    }
+
+    /// Limit the number of segments in this arena, counting segments that already exist.
+    /// [`None`] removes the limit.
+    ///
+    /// Once reached, [`Arena::try_alloc`] fails and the other allocation methods panic.
+    pub fn set_segment_limit(&self, limit: Option<usize>) {
+        self.inner.segment_limit.set(limit);
+    }
+
+    /// Reserve room for at least `additional` more values, so that allocating them one by one
+    /// never calls the allocator.
+    ///
+    /// Spare segments are linked after the current one, and used once it is full. Slices that
+    /// don't fit in them still open their own segment.
+    ///
+    /// # Panics
+    ///
+    /// Panics if this would go past the limits of the arena, see [`Arena::try_reserve`].
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let arena = Arena::<u32, 16>::new();
+    /// arena.reserve(1000);
+    ///
+    /// let segments = arena.segment_count();
+    /// for i in 0..1000 {
+    ///     arena.alloc(i);
+    /// }
+    /// assert_eq!(arena.segment_count(), segments);
+    /// ```
+    pub fn reserve(&self, additional: usize) {
+        if let Err(kind) = self.try_reserve(additional) {
+            match kind {
+                AllocErrorKind::LimitReached => {
+                    panic!("The arena has reached its allocation limit!")
+                }
+                AllocErrorKind::OutOfMemory => panic!("Out of memory while reserving arena space!"),
+            }
+        }
+    }
+
+    /// Like [`Arena::reserve`], but returns an error instead of panicking.
+    pub fn try_reserve(&self, additional: usize) -> Result<(), AllocErrorKind> {
+        self.inner.try_reserve(additional)
+    }
+
+    /// Number of values in the arena.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.inner.len.get()
+    }
+
+    /// Returns `true` if nothing has been allocated in the arena.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Number of slots in every segment of the arena, used or not.
+    ///
+    /// Slots left over at the end of a segment when a slice did not fit in it are counted, but
+    /// will never be used.
+    #[inline]
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity.get()
+    }
+
+    /// Number of segments in the arena.
+    #[inline]
+    pub fn segment_count(&self) -> usize {
+        self.inner.segment_count.get()
+    }
+
+    /// Bytes held by the segments of the arena, including their headers.
+    ///
+    /// This is what [`Arena::set_byte_limit`] is checked against.
+    #[inline]
+    pub fn allocated_bytes(&self) -> usize {
+        self.inner.allocated_bytes.get()
+    }
+
+    /// Take a snapshot of [`Arena::len`], [`Arena::capacity`], [`Arena::segment_count`] and
+    /// [`Arena::allocated_bytes`].
+    pub fn stats(&self) -> ArenaStats {
+        ArenaStats {
+            len: self.len(),
+            capacity: self.capacity(),
+            segment_count: self.segment_count(),
+            allocated_bytes: self.allocated_bytes(),
+        }
+    }
+
+    /// Move an object into the arena, and return a plain reference to its new location.
+    ///
+    /// The reference borrows this [`Arena`] handle, so the compiler proves the value is alive
+    /// and no runtime check is needed to read it. Prefer this over [`Arena::alloc`] when the
+    /// arena clearly outlives every use of the value.
+    ///
+    /// There is intentionally no `&mut T` version. The value stays reachable through
+    /// [`Arena::iter`] (and any other handle to this [`Arena`]), so a mutable reference could
+    /// alias. Use a [`Cell`] or [`RefCell`] for mutation, like with [`ArenaRef`].
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let arena = Arena::<String>::new();
+    ///
+    /// let words: Vec<&String> = ["a", "b", "c"]
+    ///     .into_iter()
+    ///     .map(|s| arena.alloc_ref(s.to_string()))
+    ///     .collect();
+    ///
+    /// assert_eq!(words.iter().map(|s| s.as_str()).collect::<String>(), "abc");
+    /// ```
+    #[inline]
+    pub fn alloc_ref(&self, cont: T) -> &T {
+        // SAFETY: the borrow of `self` keeps `inner`, and so the value, alive.
+        unsafe { &*self.inner.alloc(cont).ptr }
+    }
+
+    /// Copy a slice into the arena as one contiguous run, and return an [`ArenaSlice`] to it.
+    ///
+    /// If the run does not fit in the current segment, a new one is opened for it. Runs longer
+    /// than `N` get a segment of their own.
+    pub fn alloc_slice_copy(&self, src: &[T]) -> ArenaSlice<T, N>
+    where
+        T: Copy,
+    {
+        // SAFETY: `T: Copy`, so the source stays valid.
+        let first = unsafe { self.inner.alloc_copy(src.as_ptr(), src.len()) };
+
+        ArenaSlice::new(&self.inner, first, src.len())
+    }
+
+    /// Clone a slice into the arena as one contiguous run, and return an [`ArenaSlice`] to it.
+    ///
+    /// See [`Arena::alloc_extend`].
+    pub fn alloc_slice_clone(&self, src: &[T]) -> ArenaSlice<T, N>
+    where
+        T: Clone,
+    {
+        self.alloc_extend(src.iter().cloned())
+    }
+
+    /// Move every value of an iterator into the arena as one contiguous run, and return an
+    /// [`ArenaSlice`] to it.
+    ///
+    /// The values are collected before being moved in, so the iterator is free to allocate in
+    /// this [`Arena`] itself without splitting the run.
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let arena = Arena::<String, 4>::new();
+    ///
+    /// let words = arena.alloc_extend("the quick brown fox jumps".split(' ').map(String::from));
+    /// assert_eq!(words.len(), 5);
+    /// assert_eq!(words.join(" "), "the quick brown fox jumps");
+    /// ```
+    pub fn alloc_extend<I: IntoIterator<Item = T>>(&self, iter: I) -> ArenaSlice<T, N> {
+        let values: Vec<T> = iter.into_iter().collect();
+
+        let len = values.len();
+        let first = self.inner.alloc_vec(values);
+
+        ArenaSlice::new(&self.inner, first, len)
+    }
+
+    /// Get the value at `index`, or [`None`] if there is none, because nothing has been
+    /// allocated there or it has been removed.
+    ///
+    /// Like [`Arena::alloc_ref`], the reference borrows this [`Arena`] handle.
+    pub fn get(&self, index: ArenaIndex<T>) -> Option<&T> {
+        let slot = self.inner.locate(index.into_raw() as usize)?;
+        Some(unsafe { &*slot.ptr })
+    }
+
+    /// Get an [`ArenaRef`] to the value at `index`, or [`None`] if there is none, see
+    /// [`Arena::get`].
+    pub fn get_ref(&self, index: ArenaIndex<T>) -> Option<ArenaRef<T, N>> {
+        let slot = self.inner.locate(index.into_raw() as usize)?;
+        Some(ArenaRef::new(&self.inner, slot))
+    }
+
+    /// Create an iterator over every element in the [`Arena`], yeilding [`ArenaRef`]s
+    ///
+    /// The iterator visits the values that were in the arena when it was created, in allocation
+    /// order. Values allocated while iterating are never visited, so it is fine to allocate from
+    /// inside the loop, or to have several iterators going at once.
+    ///
+    /// Slots left by [`Arena::take`] are skipped, and are not reused while an iterator is around.
+    /// Once reused, a slot is visited in its place rather than in allocation order.
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let arena = Arena::<i32, 4>::new();
+    /// for i in 0..10 {
+    ///     arena.alloc(i);
+    /// }
+    ///
+    /// // Doubles every value, appending the results.
+    /// for x in arena.iter() {
+    ///     arena.alloc(*x * 2);
+    /// }
+    ///
+    /// assert_eq!(arena.iter().count(), 20);
+    /// ```
+    pub fn iter(&self) -> ArenaIterator<T, N> {
+        ArenaIterator {
+            cursor: self.inner.start_iteration(),
+            arena_inner: self.inner.clone(),
+        }
+    }
+
+    /// Create an iterator over every element in the [`Arena`], yielding plain references.
+    ///
+    /// Unlike [`Arena::iter`] it does not touch any reference counts, so it is the one to use
+    /// for bulk passes. It visits the same values as [`Arena::iter`] would.
+    pub fn iter_ref(&self) -> Iter<'_, T, N> {
+        Iter {
+            cursor: self.inner.start_iteration(),
+            arena_inner: &self.inner,
+        }
+    }
+
+    /// Returns `true` if this is the only [`Arena`] handle, with no [`ArenaRc`]s or
+    /// [`ArenaIterator`]s around. [`ArenaRef`]s and [`ArenaSlice`]s don't count.
+    pub fn is_unique(&self) -> bool {
+        Rc::strong_count(&self.inner) == 1
+    }
+
+    /// Move the value `r` points to out of the arena, leaving its slot to be reused by a later
+    /// allocation.
+    ///
+    /// Returns [`None`] if `r` is stale, belongs to another arena, or is part of an
+    /// [`ArenaSlice`], whose values can only go all at once with [`Arena::reset`]. Once removed,
+    /// [`ArenaRef::try_get`] on `r` and every clone of it returns [`None`], even after the slot
+    /// has been reused.
+    ///
+    /// # Safety
+    ///
+    /// Only the handles are checked. No `&T` to the value read out of an [`ArenaRef`] (through
+    /// [`Deref`] or [`ArenaRef::try_get`]) may still be alive, as it would dangle once the value
+    /// is gone.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena is shared, see [`Arena::is_unique`].
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let mut arena = Arena::<String>::new();
+    /// let a = arena.alloc("a".to_string());
+    /// let b = arena.alloc("b".to_string());
+    ///
+    /// // SAFETY: nothing borrows from `a`.
+    /// assert_eq!(unsafe { arena.take(&a) }.as_deref(), Some("a"));
+    /// assert!(a.try_get().is_none());
+    /// assert_eq!(unsafe { arena.take(&a) }, None);
+    ///
+    /// // The slot of `a` is reused, but `a` still can't see into it.
+    /// let c = arena.alloc("c".to_string());
+    /// assert_eq!(c.index(), a.index());
+    /// assert!(a.try_get().is_none());
+    ///
+    /// let values: Vec<_> = arena.iter_ref().cloned().collect();
+    /// assert_eq!(values, ["c", "b"]);
+    /// ```
+    pub unsafe fn take(&mut self, r: &ArenaRef<T, N>) -> Option<T> {
+        assert!(
+            self.is_unique(),
+            "Can't remove values from an arena that is still shared!"
+        );
+
+        if !std::ptr::eq(r.arena.as_ptr(), Rc::as_ptr(&self.inner))
+            || !r.is_live()
+            || r.stamp == PINNED
+        {
+            return None;
+        }
+
+        // SAFETY: `r` is live, so its slot holds a value, and the arena is borrowed mutably.
+        let (segment, i) = self.inner.find(r.index as usize)?;
+        Some(unsafe { self.inner.take(segment, i) })
+    }
+
+    /// Like [`Arena::take`], but drops the value. Returns whether there was one to remove.
+    ///
+    /// # Safety
+    ///
+    /// See [`Arena::take`].
+    pub unsafe fn remove(&mut self, r: &ArenaRef<T, N>) -> bool {
+        unsafe { self.take(r) }.is_some()
+    }
+
+    /// Drop every value in the arena, but keep its segments around to be reused.
+    ///
+    /// Every [`ArenaRef`] and [`ArenaSlice`] handed out before goes stale: [`ArenaRef::try_get`]
+    /// returns [`None`] even though the arena is still alive, so they never see the values that
+    /// reuse their slots.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena is shared, see [`Arena::is_unique`].
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let mut arena = Arena::<i32, 16>::new();
+    ///
+    /// for frame in 0..3 {
+    ///     let r = arena.alloc(frame);
+    ///     arena.alloc_extend(0..100);
+    ///
+    ///     arena.reset();
+    ///     assert!(r.try_get().is_none());
+    /// }
+    ///
+    /// // The segments from the first frame were reused for the others.
+    /// assert_eq!(arena.segment_count(), 2);
+    /// ```
+    pub fn reset(&mut self) {
+        self.reset_retaining(usize::MAX);
+    }
+
+    /// Like [`Arena::reset`], but only keep the first `segments` segments (and always at least
+    /// one), freeing the others.
+    pub fn reset_retaining(&mut self, segments: usize) {
+        assert!(
+            self.is_unique(),
+            "Can't reset an arena that is still shared!"
+        );
+
+        match self.inner.generation.get().checked_add(1) {
+            Some(generation) => self.inner.generation.set(generation),
+            None => {
+                // Out of generations. Moving the segments over to a fresh `ArenaInner` leaves
+                // every outstanding `ArenaRef` with a dead `Weak` instead.
+                let segments = std::mem::take(&mut *self.inner.segments.borrow_mut());
+
+                let fresh = ArenaInner::from_segments(segments);
+                fresh.segment_limit.set(self.inner.segment_limit.get());
+                fresh.byte_limit.set(self.inner.byte_limit.get());
+                self.inner = Rc::new(fresh);
+            }
+        }
+
+        let freed = self.inner.rewind(segments);
+
+        for segment in self.inner.segments.borrow().iter() {
+            unsafe { segment.as_ref() }.clear();
+        }
+        for segment in freed {
+            drop(unsafe { Box::from_raw(segment.as_ptr()) });
+        }
+    }
+
+    /// Pack every value into a single dense segment, freeing the segments left half-empty by
+    /// [`Arena::take`] and [`Arena::remove`]. Values keep their order.
+    ///
+    /// Returns the new [`ArenaIndex`] of every value by its old one, to update indexes kept
+    /// elsewhere. See [`Arena::compact_nodes`] to rewire the refs values hold instead.
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let mut arena = Arena::<i32, 4>::new();
+    /// let indexes: Vec<_> = (0..16).map(|i| arena.alloc(i).index()).collect();
+    /// for &index in &indexes[..12] {
+    ///     unsafe { arena.remove(&arena.get_ref(index).unwrap()) };
+    /// }
+    ///
+    /// let moved = arena.compact();
+    /// assert_eq!(arena.capacity(), 4);
+    /// assert_eq!(arena.get(moved[&indexes[12]]), Some(&12));
+    /// ```
+    ///
+    /// # Panics
+    ///
+    /// Panics unless this is the only handle to the arena, with no [`ArenaRef`]s or any other
+    /// handles around, like [`Arena::reset`].
+    pub fn compact(&mut self) -> HashMap<ArenaIndex<T>, ArenaIndex<T>> {
+        assert!(
+            Rc::get_mut(&mut self.inner).is_some(),
+            "Can't compact an arena that is still shared or referenced!"
+        );
+
+        self.compact_with(|_, _| ())
+            .into_iter()
+            .enumerate()
+            .map(|(new, old)| (ArenaIndex::from_usize(old), ArenaIndex::from_usize(new)))
+            .collect()
+    }
+
+    /// Move every value over to a fresh `ArenaInner` with a single dense segment, passing each
+    /// one to `rewire` on the way along with a map from refs into this arena to refs into the
+    /// new one. Returns the old index of every value, in their new order.
+    fn compact_with(
+        &mut self,
+        mut rewire: impl FnMut(&mut T, &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>),
+    ) -> Vec<usize> {
+        let len = self.inner.len.get();
+        let mut values = Vec::with_capacity(len);
+        let mut slots = Vec::with_capacity(len);
+
+        // SAFETY: the values are forgotten by their segments right after, and nothing in between
+        //         can panic.
+        let mut cursor = Cursor::new(&self.inner);
+        while let Some(slot) = cursor.nth(&self.inner, 0) {
+            values.push(unsafe { slot.ptr.read() });
+            slots.push((slot.index, unsafe { &*slot.stamp }.get()));
+        }
+        for segment in self.inner.segments.borrow().iter() {
+            let segment = unsafe { segment.as_ref() };
+            segment.length.set(0);
+            segment.live.set(0);
+        }
+
+        let fresh = ArenaInner::with_capacity(len.max(1).next_multiple_of(N));
+        fresh.segment_limit.set(self.inner.segment_limit.get());
+        fresh.byte_limit.set(self.inner.byte_limit.get());
+
+        // The old segments are freed, and every ref into them is left with a dead `Weak`. Holding
+        // on to one keeps the address from being reused by another arena while rewiring.
+        let old = Rc::downgrade(&self.inner);
+        let generation = self.inner.generation.get();
+        self.inner = Rc::new(fresh);
+
+        let first = self.inner.segment(0).unwrap();
+        let mut map = |r: &ArenaRef<T, N>| {
+            let position = (r.arena.ptr_eq(&old) && r.generation == generation)
+                .then(|| slots.binary_search_by_key(&(r.index as usize), |&(index, _)| index))
+                .and_then(Result::ok)
+                .filter(|&position| slots[position].1 == r.stamp);
+
+            match position {
+                Some(position) => {
+                    let mut moved = ArenaRef::new(&self.inner, first.slot(position));
+
+                    // The stamp the slot gets once `alloc_spread` moves the value in.
+                    moved.stamp = 1;
+                    moved
+                }
+                None => r.clone(),
+            }
+        };
+
+        // If this panics the values are dropped, leaving the arena empty.
+        for value in &mut values {
+            rewire(value, &mut map);
+        }
+
+        self.inner.alloc_spread(values, |_| ());
+        slots.into_iter().map(|(index, _)| index).collect()
+    }
+
+    /// Turn this arena into an iterator that moves every value out, in allocation order.
+    ///
+    /// Returns the arena back unless this is the last [`Arena`] handle, with no [`ArenaRc`]s or
+    /// [`ArenaIterator`]s around. Any [`ArenaRef`] left will act like the arena was dropped.
+    pub fn try_into_iter(self) -> Result<IntoIter<T, N>, Self> {
+        match Rc::try_unwrap(self.inner) {
+            Ok(inner) => Ok(IntoIter {
+                cursor: Cursor::new(&inner),
+                arena_inner: inner,
+            }),
+            Err(inner) => Err(Arena { inner }),
+        }
+    }
+
+    /// Move every value out of this arena into a [`Vec`], in allocation order.
+    ///
+    /// Returns the arena back if it is shared, see [`Arena::try_into_iter`].
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let arena = Arena::<String>::new();
+    /// let r = arena.alloc("a".to_string());
+    /// arena.alloc("b".to_string());
+    ///
+    /// let other = arena.clone();
+    /// let arena = arena.into_vec().unwrap_err();
+    /// drop(other);
+    ///
+    /// assert_eq!(arena.into_vec().ok().unwrap(), ["a", "b"]);
+    /// assert!(r.try_get().is_none());
+    /// ```
+    pub fn into_vec(self) -> Result<Vec<T>, Self> {
+        self.try_into_iter().map(Iterator::collect)
+    }
+
+    /// Create an iterator over every element in the [`Arena`], yielding mutable references.
+    ///
+    /// Returns [`None`] unless this is the only handle to the arena, with no other [`Arena`]s,
+    /// [`ArenaRef`]s, [`ArenaRc`]s or [`ArenaSlice`]s around, just like [`Rc::get_mut`].
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    ///
+    /// let mut arena = Arena::<i32>::new();
+    /// arena.alloc_extend(0..10);
+    ///
+    /// for x in arena.iter_mut().unwrap() {
+    ///     *x *= 2;
+    /// }
+    ///
+    /// let r = arena.alloc(20);
+    /// assert!(arena.iter_mut().is_none());
+    /// ```
+    pub fn iter_mut(&mut self) -> Option<IterMut<'_, T, N>> {
+        let inner = &*Rc::get_mut(&mut self.inner)?;
+
+        Some(IterMut {
+            cursor: Cursor::new(inner),
+            arena_inner: inner,
+            _marker: PhantomData,
+        })
+    }
+}
+
+impl<T, const N: usize> Clone for Arena<T, N> {
+    fn clone(&self) -> Self {
+        Arena {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T, const N: usize> Default for Arena<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Values are collected first, then moved in a segment at a time, after every other value.
+impl<T, const N: usize> Extend<T> for Arena<T, N> {
+    fn extend<I: IntoIterator<Item = T>>(&mut self, iter: I) {
+        self.inner.alloc_spread(iter.into_iter().collect(), |_| ());
+    }
+}
+
+impl<'a, T: Copy + 'a, const N: usize> Extend<&'a T> for Arena<T, N> {
+    fn extend<I: IntoIterator<Item = &'a T>>(&mut self, iter: I) {
+        self.extend(iter.into_iter().copied());
+    }
+}
+
+impl<T, const N: usize> FromIterator<T> for Arena<T, N> {
+    fn from_iter<I: IntoIterator<Item = T>>(iter: I) -> Self {
+        Vec::from_iter(iter).into()
+    }
+}
+
+/// Moves the values into an arena with just enough room for them.
+impl<T, const N: usize> From<Vec<T>> for Arena<T, N> {
+    fn from(values: Vec<T>) -> Self {
+        let arena = Arena::with_capacity(values.len());
+        arena.inner.alloc_spread(values, |_| ());
+        arena
+    }
+}
+
+impl<T, const N: usize> PartialEq for Arena<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a Arena<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter_ref()
+    }
+}
+
+/// Moves every value out of the arena.
+///
+/// # Panics
+///
+/// Panics if the arena is shared, see [`Arena::try_into_iter`] for a version that does not.
+impl<T, const N: usize> IntoIterator for Arena<T, N> {
+    type Item = T;
+    type IntoIter = IntoIter<T, N>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        match self.try_into_iter() {
+            Ok(iter) => iter,
+            Err(_) => panic!("Can't move values out of an arena that is still shared!"),
+        }
+    }
+}
+
+/// Walks the values of an arena from both ends, shared by every iterator.
+///
+/// It visits the values that were in the arena when it was created, as segments before the tail
+/// never grow. Skipping costs one step per segment rather than per value.
+struct Cursor<T> {
+    // Next slot to yield from the front.
+    front_segment: usize,
+    front: NonNull<Segment<T>>,
+    front_pos: usize,
+
+    // One past the next slot to yield from the back.
+    back_segment: usize,
+    back: NonNull<Segment<T>>,
+    back_pos: usize,
+
+    remaining: usize,
+
+    // Entries of `ArenaInner::late` when created. Slots listed after them were filled later on.
+    late: usize,
+}
+
+/// Whether the occupied slot `i` of `segment` was filled after a cursor that saw `seen` entries
+/// of `ArenaInner::late` was created.
+#[inline]
+fn is_late<T>(late: &HashMap<usize, usize>, seen: usize, segment: &Segment<T>, i: usize) -> bool {
+    segment.late.get() != 0
+        && late
+            .get(&(segment.base.get() + i))
+            .is_some_and(|&order| order >= seen)
+}
+
+impl<T> Cursor<T> {
+    fn new<const N: usize>(inner: &ArenaInner<T, N>) -> Cursor<T> {
+        let segments = inner.segments.borrow();
+        let back_segment = inner.tail_index.get();
+        let back = segments[back_segment];
+
+        Cursor {
+            front_segment: 0,
+            front: segments[0],
+            front_pos: 0,
+
+            back_segment,
+            back,
+            back_pos: unsafe { back.as_ref() }.length.get(),
+
+            remaining: inner.len.get(),
+            late: inner.late.borrow().len(),
+        }
+    }
+
+    /// Slots `front_pos` may go up to within the front segment.
+    fn front_end(&self) -> usize {
+        if self.front_segment == self.back_segment {
+            self.back_pos
+        } else {
+            unsafe { self.front.as_ref() }.length.get()
+        }
+    }
+
+    /// Slots `back_pos` may go down to within the back segment.
+    fn back_start(&self) -> usize {
+        if self.front_segment == self.back_segment {
+            self.front_pos
+        } else {
+            0
+        }
+    }
+
+    fn nth<const N: usize>(&mut self, inner: &ArenaInner<T, N>, mut n: usize) -> Option<Slot<T>> {
+        if n >= self.remaining {
+            self.remaining = 0;
+            self.front_segment = self.back_segment;
+            self.front = self.back;
+            self.front_pos = self.back_pos;
+            return None;
+        }
+
+        self.remaining -= n + 1;
+        let late = inner.late.borrow();
+
+        loop {
+            let segment = unsafe { &*self.front.as_ptr() };
+            let end = self.front_end();
+
+            if segment.is_dense() && segment.late.get() == 0 {
+                let available = end - self.front_pos;
+
+                if n < available {
+                    self.front_pos += n + 1;
+                    return Some(segment.slot(self.front_pos - 1));
+                }
+
+                n -= available;
+            } else if self.front_pos == 0
+                && self.front_segment != self.back_segment
+                && n >= segment.live.get()
+                && segment.late.get() == 0
+            {
+                n -= segment.live.get();
+            } else {
+                // Removed values leave holes, so every slot has to be looked at.
+                for i in self.front_pos..end {
+                    if segment.is_occupied(i) && !is_late(&late, self.late, segment, i) {
+                        if n == 0 {
+                            self.front_pos = i + 1;
+                            return Some(segment.slot(i));
+                        }
+                        n -= 1;
+                    }
+                }
+            }
+
+            self.front_segment += 1;
+            self.front = inner.segments.borrow()[self.front_segment];
+            self.front_pos = 0;
+        }
+    }
+
+    fn nth_back<const N: usize>(
+        &mut self,
+        inner: &ArenaInner<T, N>,
+        mut n: usize,
+    ) -> Option<Slot<T>> {
+        if n >= self.remaining {
+            self.remaining = 0;
+            self.back_segment = self.front_segment;
+            self.back = self.front;
+            self.back_pos = self.front_pos;
+            return None;
+        }
+
+        self.remaining -= n + 1;
+        let late = inner.late.borrow();
+
+        loop {
+            let segment = unsafe { &*self.back.as_ptr() };
+            let start = self.back_start();
+
+            if segment.is_dense() && segment.late.get() == 0 {
+                let available = self.back_pos - start;
+
+                if n < available {
+                    self.back_pos -= n + 1;
+                    return Some(segment.slot(self.back_pos));
+                }
+
+                n -= available;
+            } else if self.back_pos == segment.length.get()
+                && self.front_segment != self.back_segment
+                && n >= segment.live.get()
+                && segment.late.get() == 0
+            {
+                n -= segment.live.get();
+            } else {
+                for i in (start..self.back_pos).rev() {
+                    if segment.is_occupied(i) && !is_late(&late, self.late, segment, i) {
+                        if n == 0 {
+                            self.back_pos = i;
+                            return Some(segment.slot(i));
+                        }
+                        n -= 1;
+                    }
+                }
+            }
+
+            self.back_segment -= 1;
+            self.back = inner.segments.borrow()[self.back_segment];
+            self.back_pos = unsafe { self.back.as_ref() }.length.get();
+        }
+    }
+}
+
+/// An iterator over every element in the [`Arena`], yeilding [`ArenaRef`]. 
+///
+/// It can be walked from both ends, and skipping with [`Iterator::nth`] costs one step per
+/// segment rather than per value.
+pub struct ArenaIterator<T: Sized, const N: usize> {
+    arena_inner: Rc<ArenaInner<T, N>>,
+    cursor: Cursor<T>,
+}
+
+impl<T, const N: usize> Drop for ArenaIterator<T, N> {
+    fn drop(&mut self) {
+        self.arena_inner.end_iteration();
+    }
+}
+
+impl<T, const N: usize> Iterator for ArenaIterator<T, N> {
+    type Item = ArenaRef<T, N>;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let slot = self.cursor.nth(&self.arena_inner, n)?;
+        Some(ArenaRef::new(&self.arena_inner, slot))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cursor.remaining, Some(self.cursor.remaining))
+    }
+
+    fn count(self) -> usize {
+        self.cursor.remaining
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for ArenaIterator<T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let slot = self.cursor.nth_back(&self.arena_inner, n)?;
+        Some(ArenaRef::new(&self.arena_inner, slot))
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for ArenaIterator<T, N> {}
+
+impl<T, const N: usize> FusedIterator for ArenaIterator<T, N> {}
+
+/// An iterator over every element in the [`Arena`], yielding `&T`, see [`Arena::iter_ref`].
+pub struct Iter<'a, T: Sized, const N: usize> {
+    arena_inner: &'a ArenaInner<T, N>,
+    cursor: Cursor<T>,
+}
+
+impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        let ptr = self.cursor.nth(self.arena_inner, n)?.ptr;
+        Some(unsafe { &*ptr })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cursor.remaining, Some(self.cursor.remaining))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for Iter<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let ptr = self.cursor.nth_back(self.arena_inner, n)?.ptr;
+        Some(unsafe { &*ptr })
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for Iter<'_, T, N> {}
+
+impl<T, const N: usize> Drop for Iter<'_, T, N> {
+    fn drop(&mut self) {
+        self.arena_inner.end_iteration();
+    }
+}
+
+impl<T, const N: usize> FusedIterator for Iter<'_, T, N> {}
+
+/// An iterator over every element in the [`Arena`], yielding `&mut T`, see
+/// [`Arena::iter_mut`].
+pub struct IterMut<'a, T: Sized, const N: usize> {
+    arena_inner: &'a ArenaInner<T, N>,
+    cursor: Cursor<T>,
+    _marker: PhantomData<&'a mut T>,
+}
+
+impl<'a, T, const N: usize> Iterator for IterMut<'a, T, N> {
+    type Item = &'a mut T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // SAFETY: the arena is borrowed mutably, and the cursor yields every slot at most once.
+        let ptr = self.cursor.nth(self.arena_inner, n)?.ptr;
+        Some(unsafe { &mut *ptr })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cursor.remaining, Some(self.cursor.remaining))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IterMut<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.nth_back(0)
+    }
+
+    fn nth_back(&mut self, n: usize) -> Option<Self::Item> {
+        let ptr = self.cursor.nth_back(self.arena_inner, n)?.ptr;
+        Some(unsafe { &mut *ptr })
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IterMut<'_, T, N> {}
+
+impl<T, const N: usize> FusedIterator for IterMut<'_, T, N> {}
+
+/// An iterator moving every value out of an [`Arena`], see [`Arena::try_into_iter`].
+pub struct IntoIter<T: Sized, const N: usize> {
+    arena_inner: ArenaInner<T, N>,
+    cursor: Cursor<T>,
+}
+
+impl<T, const N: usize> Iterator for IntoIter<T, N> {
+    type Item = T;
+    fn next(&mut self) -> Option<Self::Item> {
+        self.nth(0)
+    }
+
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        // The values skipped over still have to be dropped.
+        for _ in 0..n {
+            self.next()?;
+        }
+
+        // SAFETY: the cursor yields every slot at most once, so each value is read once.
+        let ptr = self.cursor.nth(&self.arena_inner, 0)?.ptr;
+        Some(unsafe { ptr.read() })
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.cursor.remaining, Some(self.cursor.remaining))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for IntoIter<T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let ptr = self.cursor.nth_back(&self.arena_inner, 0)?.ptr;
+        Some(unsafe { ptr.read() })
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for IntoIter<T, N> {}
+
+impl<T, const N: usize> FusedIterator for IntoIter<T, N> {}
+
+impl<T, const N: usize> Drop for IntoIter<T, N> {
+    fn drop(&mut self) {
+        // However dropping the rest goes, the segments must not drop any value a second time.
+        struct ForgetValues<'a, T, const N: usize>(&'a ArenaInner<T, N>);
+
+        impl<T, const N: usize> Drop for ForgetValues<'_, T, N> {
+            fn drop(&mut self) {
+                for segment in self.0.segments.borrow().iter() {
+                    let segment = unsafe { segment.as_ref() };
+                    segment.length.set(0);
+                    segment.live.set(0);
+                }
+            }
+        }
+
+        let _forget = ForgetValues(&self.arena_inner);
+        while let Some(slot) = self.cursor.nth(&self.arena_inner, 0) {
+            drop(unsafe { slot.ptr.read() });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    #[test]
+    fn basic_usage() {
+        let arena: Arena<Cell<u32>, 8> = Arena::new();
+        for i in 0..128 {
+            let r = arena.alloc(Cell::new(i));
+            // Test value
+            assert_eq!(r.get(), i);
+
+            // Test mutibility
+            assert_eq!(r.replace(1), i);
+
+            // Ensure set
+            assert_eq!(r.get(), 1);
+        }
+
+        let r = arena.alloc(Cell::new(1));
+        assert_eq!(r.get(), 1);
+
+        drop(arena);
+
+        assert_eq!(r.try_get(), None);
+    }
+
+    #[test]
+    fn usage_guide_from_readme() {
+        // Making an arena
+        type MyType = Cell<i32>;
+        let arena: Arena<MyType> = Arena::new();
+
+        // Allocating objects:
+        //
+        // These can be treated as &'arena MyType objects. You should wrap
+        // them in a Cell or RefCell to mutate.
+
+        let obj1: ArenaRef<MyType, _> = arena.alloc(Cell::new(0));
+        let obj2: ArenaRef<MyType, _> = arena.alloc(Cell::new(1));
+        let obj3: ArenaRef<MyType, _> = arena.alloc(Cell::new(2));
+
+        assert_eq!(obj1.get(), 0); //> 0
+        assert_eq!(obj2.get(), 1); //> 1
+
+        obj3.set(-99);
+        assert_eq!(obj3.get(), -99); //> -99
+
+        // You can clone the Arena or ArenaRefs as much as you like (just like an Rc)
+        let arena2 = arena.clone();
+        let _obj1 = obj1.clone();
+
+        // And you can even get an Arena from an ArenaRef (though this is not recommended).
+        let arena3: Option<Arena<MyType>> = obj1.get_arena();
+
+        // But fair warning! An ArenaRef will NOT keep the arena alive!
+
+        // And it is YOUR RESPONSIBILITY to keep the arena alive
+        drop(arena);
+        drop(arena2);
+        drop(arena3);
+
+        // let value = &*obj1; // PANICS
+
+        // You can test if your arena is still valid:
+        let value: Option<&MyType> = obj1.try_get();
+        assert_eq!(value, None)
+    }
+
+
+    #[test]
+    fn test_iter(){
+        let arena: Arena<i32, 32> = Arena::new();
+        for i in 0..1024 {
+            arena.alloc(i);
+        }
+
+        let lhs : Vec<i32> = arena.iter().map(|x| *x).collect();
+        let rhs : Vec<i32> = (0..1024).collect();
+        assert_eq!(lhs, rhs);
+
+
+
+    }
+
+    #[test]
+    fn interleaved_iterators() {
+        let arena: Arena<u32, 4> = Arena::new();
+        for i in 0..10 {
+            arena.alloc(i);
+        }
+
+        // Two live iterators over the same chain, stepped in lockstep.
+        let pairs: Vec<(u32, u32)> = arena
+            .iter()
+            .zip(arena.iter().skip(1))
+            .map(|(a, b)| (*a, *b))
+            .collect();
+        assert_eq!(pairs, (0..9).map(|i| (i, i + 1)).collect::<Vec<_>>());
+
+        // Nested iteration sees the whole arena every time.
+        let mut count = 0;
+        for _ in arena.iter() {
+            count += arena.iter().count();
+        }
+        assert_eq!(count, 100);
+    }
+
+    #[test]
+    fn alloc_during_iteration() {
+        let arena: Arena<u32, 4> = Arena::new();
+        for i in 0..6 {
+            arena.alloc(i);
+        }
+
+        let mut iter = arena.iter();
+        let mut seen = vec![];
+        for x in iter.by_ref() {
+            seen.push(*x);
+
+            // Fills the tail, opens new segments and adds a slice, none of which are visited.
+            arena.alloc(*x + 100);
+            arena.alloc_slice_copy(&[1000; 5]);
+        }
+        assert_eq!(seen, (0..6).collect::<Vec<_>>());
+
+        // Stays exhausted, even after more allocations.
+        arena.alloc(7);
+        assert!(iter.next().is_none());
+
+        // A fresh iterator sees everything.
+        assert_eq!(arena.iter().count(), 6 + 6 * 6 + 1);
+        assert_eq!(arena.iter().count(), arena.len());
+    }
+
+    #[test]
+    fn double_ended_iteration() {
+        let arena: Arena<u32, 4> = Arena::new();
+        for i in 0..6 {
+            arena.alloc(i);
+        }
+        // Leaves two unused slots at the end of the second segment, then a spare segment.
+        arena.alloc_slice_copy(&[6, 7, 8]);
+        arena.alloc(9);
+        arena.reserve(10);
+
+        let rev: Vec<u32> = arena.iter().rev().map(|x| *x).collect();
+        assert_eq!(rev, (0..10).rev().collect::<Vec<_>>());
+
+        let mut iter = arena.iter();
+        assert_eq!(iter.len(), 10);
+        assert_eq!(*iter.next().unwrap(), 0);
+        assert_eq!(*iter.next_back().unwrap(), 9);
+        assert_eq!(*iter.nth(4).unwrap(), 5);
+        assert_eq!(iter.len(), 3);
+        assert_eq!(*iter.nth_back(1).unwrap(), 7);
+        assert_eq!(*iter.next().unwrap(), 6);
+        assert_eq!(iter.len(), 0);
+        assert!(iter.next().is_none());
+        assert!(iter.next_back().is_none());
+
+        let mut iter = arena.iter();
+        assert!(iter.nth(10).is_none());
+        assert!(iter.next().is_none());
+
+        for n in 0..10 {
+            assert_eq!(*arena.iter().nth(n).unwrap(), n as u32);
+            assert_eq!(*arena.iter().nth_back(n).unwrap(), 9 - n as u32);
+        }
+
+        let empty: Arena<u32, 4> = Arena::new();
+        assert!(empty.iter().next_back().is_none());
+    }
+
+    #[test]
+    fn borrowing_iterators() {
+        let mut arena: Arena<u32, 4> = Arena::new();
+        arena.alloc_extend(0..10);
+        arena.alloc(10);
+
+        let sum: u32 = arena.iter_ref().sum();
+        assert_eq!(sum, 55);
+        assert_eq!(
+            arena.iter_ref().rev().copied().collect::<Vec<_>>(),
+            (0..11).rev().collect::<Vec<_>>()
+        );
+        assert_eq!((&arena).into_iter().len(), 11);
+
+        for x in arena.iter_mut().unwrap() {
+            *x *= 2;
+        }
+        assert_eq!(
+            arena.iter_ref().copied().collect::<Vec<_>>(),
+            (0..11).map(|x| x * 2).collect::<Vec<_>>()
+        );
+
+        // Any other handle or reference makes it shared.
+        let other = arena.clone();
+        assert!(arena.iter_mut().is_none());
+        drop(other);
+
+        let r = arena.iter().next().unwrap();
+        assert!(arena.iter_mut().is_none());
+        drop(r);
+
+        assert!(arena.iter_mut().is_some());
+    }
+
+    #[test]
+    fn into_iter_moves_values() {
+        let counter = Rc::new(());
+        let arena: Arena<(u32, Rc<()>), 4> = Arena::new();
+        for i in 0..10 {
+            arena.alloc((i, counter.clone()));
+        }
+        let r = arena.iter().next().unwrap();
+
+        let shared = arena.clone();
+        let arena = arena.try_into_iter().err().unwrap();
+        drop(shared);
+
+        let mut iter = arena.into_iter();
+        assert_eq!(iter.next().unwrap().0, 0);
+        assert_eq!(iter.next_back().unwrap().0, 9);
+        assert_eq!(iter.nth(2).unwrap().0, 3);
+        assert_eq!(iter.len(), 5);
+        assert!(r.try_get().is_none());
+
+        // The rest are dropped exactly once along with the iterator.
+        assert_eq!(Rc::strong_count(&counter), 6);
+        drop(iter);
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        let arena: Arena<String, 2> = Arena::new();
+        arena.alloc_extend(["a", "b", "c"].map(String::from));
+        arena.alloc("d".to_string());
+        assert_eq!(arena.into_vec().ok().unwrap(), ["a", "b", "c", "d"]);
+    }
+
+    #[test]
+    fn reset_reuses_segments() {
+        let counter = Rc::new(());
+        let mut arena: Arena<Rc<()>, 4> = Arena::new();
+
+        let r = arena.alloc(counter.clone());
+        arena.alloc_extend((0..10).map(|_| counter.clone()));
+        let stats = arena.stats();
+        let first: *const Rc<()> = &*arena.iter().next().unwrap();
+
+        arena.reset();
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert!(r.try_get().is_none());
+        assert!(r.get_arena().is_none());
+        assert!(arena.is_empty());
+        assert_eq!(arena.iter().count(), 0);
+
+        // Same segments, starting over from the first.
+        assert_eq!(arena.segment_count(), stats.segment_count);
+        assert_eq!(arena.allocated_bytes(), stats.allocated_bytes);
+        let r = arena.alloc(counter.clone());
+        assert_eq!(&*r as *const Rc<()>, first);
+        assert_eq!(r.index().into_raw(), 0);
+
+        arena.reset_retaining(1);
+        assert_eq!(arena.segment_count(), 1);
+        assert_eq!(arena.capacity(), 4);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn stale_refs_after_reset() {
+        let mut arena: Arena<i32, 4> = Arena::new();
+
+        let old = arena.alloc(1);
+        let old_slice = arena.alloc_slice_copy(&[2, 3]);
+        arena.reset();
+
+        // Same slot, same live arena, but a different generation.
+        let new = arena.alloc(10);
+        assert_eq!(new.index(), old.index());
+        assert!(old.try_get().is_none());
+        assert!(old.upgrade().is_none());
+        assert!(!old.ptr_eq(&new));
+        assert!(old_slice.try_get().is_none());
+        assert!(old_slice.get_ref(0).unwrap().try_get().is_none());
+        assert_eq!(*new, 10);
+        assert!(new.get_arena() == Some(arena.clone()));
+
+        // Running out of generations swaps in a fresh arena instead.
+        arena.inner.generation.set(u32::MAX);
+        let last = arena.alloc(20);
+        arena.reset();
+        assert!(last.try_get().is_none());
+        assert!(new.try_get().is_none());
+        assert_eq!(*arena.alloc(30), 30);
+    }
+
+    #[test]
+    #[should_panic(expected = "still shared")]
+    fn reset_shared_arena() {
+        let mut arena: Arena<u32> = Arena::new();
+        let _r = arena.alloc_rc(1);
+
+        arena.reset();
+    }
+
+    #[test]
+    #[cfg(target_pointer_width = "64")]
+    fn arena_ref_size() {
+        // The `Weak`, with the generation, stamp and index packed behind it.
+        assert_eq!(std::mem::size_of::<ArenaRef<u64, 16>>(), 24);
+    }
+
+    #[test]
+    fn remove_and_reuse_slots() {
+        let counter = Rc::new(());
+        let mut arena: Arena<(usize, Rc<()>), 4> = Arena::new();
+
+        let refs: Vec<_> = (0..12).map(|i| arena.alloc((i, counter.clone()))).collect();
+        let slice = arena.alloc_extend((12..14).map(|i| (i, counter.clone())));
+
+        for r in refs.iter().step_by(3).chain(&refs[4..6]) {
+            assert!(unsafe { arena.remove(r) });
+        }
+        assert_eq!(Rc::strong_count(&counter), 1 + 8);
+        assert_eq!(arena.len(), 8);
+
+        // Removed values are detected, and can't be removed twice.
+        assert!(refs[0].try_get().is_none());
+        assert!(refs[0].upgrade().is_none());
+        assert!(!unsafe { arena.remove(&refs[0]) });
+        assert_eq!(arena.get(refs[0].index()), None);
+        assert_eq!(refs[1].0, 1);
+
+        // Values of a slice can't be taken out one by one, nor can values of another arena.
+        assert!(unsafe { arena.take(&slice.get_ref(0).unwrap()) }.is_none());
+        assert!(!unsafe { arena.remove(&Arena::new().alloc((0, counter.clone()))) });
+
+        let values = |arena: &Arena<(usize, Rc<()>), 4>| -> Vec<usize> {
+            arena.iter_ref().map(|(i, _)| *i).collect()
+        };
+        let live = [1, 2, 7, 8, 10, 11, 12, 13];
+        assert_eq!(values(&arena), live);
+        assert_eq!(arena.iter().rev().map(|r| r.0).collect::<Vec<_>>(), {
+            let mut live = live;
+            live.reverse();
+            live
+        });
+        for n in 0..live.len() {
+            assert_eq!(arena.iter_ref().nth(n).unwrap().0, live[n]);
+            assert_eq!(
+                arena.iter_ref().nth_back(n).unwrap().0,
+                live[live.len() - 1 - n]
+            );
+        }
+        assert_eq!(arena.iter().len(), live.len());
+
+        // Freed slots are not reused while an iterator is around.
+        let iter = arena.iter();
+        let behind = arena.alloc((14, counter.clone()));
+        assert_eq!(behind.index().into_raw(), 14);
+        assert_eq!(iter.count(), live.len());
+
+        // The slot is reused afterwards, without reviving the old handles.
+        let segments = arena.segment_count();
+        let reused: Vec<_> = (15..21)
+            .map(|i| arena.alloc((i, counter.clone())))
+            .collect();
+        assert_eq!(arena.segment_count(), segments);
+        assert!(
+            refs.iter()
+                .all(|r| r.try_get().is_none_or(|(i, _)| live.contains(i)))
+        );
+        assert!(reused.iter().any(|r| r.index() == refs[0].index()));
+        assert_eq!(arena.len(), 15);
+
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn worn_out_slots_are_retired() {
+        let mut arena: Arena<i32, 4> = Arena::new();
+        let r = arena.alloc(0);
+
+        // Pretend the slot has already been reused over and over.
+        let segment = arena.inner.segment(0).unwrap();
+        segment.stamps[0].set(RETIRED - 1);
+        let r = arena.get_ref(r.index()).unwrap();
+
+        assert!(unsafe { arena.remove(&r) });
+        assert_eq!(arena.alloc(1).index().into_raw(), 1);
+
+        // Until the next reset.
+        arena.reset();
+        assert_eq!(arena.alloc(2).index().into_raw(), 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "still shared")]
+    fn remove_from_shared_arena() {
+        let mut arena: Arena<i32> = Arena::new();
+        let r = arena.alloc(1);
+        let _iter = arena.iter();
+
+        unsafe { arena.remove(&r) };
+    }
+
+    #[test]
+    fn collection_traits() {
+        #[derive(Default)]
+        struct Graph {
+            nodes: Arena<u32, 4>,
+        }
+
+        let mut graph = Graph::default();
+        graph.nodes.alloc(0);
+
+        // Fills the rest of the tail, then whole segments.
+        graph.nodes.extend(1..10);
+        graph.nodes.extend(&[10, 11]);
+        assert_eq!(graph.nodes.segment_count(), 3);
+        assert!(graph.nodes.iter_ref().copied().eq(0..12));
+
+        let arena: Arena<u32, 4> = (0..10).collect();
+        assert!(arena.iter_ref().copied().eq(0..10));
+
+        let arena: Arena<u32, 4> = Arena::from(vec![1, 2, 3, 4, 5]);
+        assert_eq!(arena.segment_count(), 1);
+        assert_eq!(arena.capacity(), 8);
+
+        let (arena, refs) = Arena::<u32, 4>::from_iter_with_refs(0..6);
+        assert_eq!(refs.len(), 6);
+        for (i, r) in refs.iter().enumerate() {
+            assert_eq!(**r, i as u32);
+            assert!(arena.get_ref(r.index()).unwrap().ptr_eq(r));
+        }
+    }
+
+    #[test]
+    fn extend_past_limit_drops_once() {
+        let counter = Rc::new(());
+        let mut arena: Arena<Rc<()>, 4> = Arena::new();
+        arena.set_segment_limit(Some(2));
+
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            arena.extend((0..10).map(|_| counter.clone()));
+        }));
+        assert!(result.is_err());
+        assert_eq!(arena.len(), 8);
+        assert_eq!(Rc::strong_count(&counter), 9);
+
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn arena_ref_traits() {
+        use std::collections::{BTreeSet, HashMap, HashSet};
+
+        let arena: Arena<String> = Arena::new();
+        let words: Vec<_> = ["b", "a", "c", "a"]
+            .into_iter()
+            .map(|word| arena.alloc(word.to_string()))
+            .collect();
+
+        // By value, like an `Rc`.
+        assert_eq!(words[1], words[3]);
+        assert!(words[0] > words[1]);
+        let sorted: BTreeSet<_> = words.iter().cloned().collect();
+        assert_eq!(sorted.len(), 3);
+
+        let lengths: HashMap<_, _> = words.iter().map(|w| (w.clone(), w.len())).collect();
+        let key = String::from("c");
+        assert_eq!(lengths.get(&key), Some(&1));
+        assert_eq!(words[2].as_ref(), "c");
+
+        // By address.
+        let unique: HashSet<_> = words.iter().cloned().map(ByAddress).collect();
+        assert_eq!(unique.len(), 4);
+        assert!(unique.contains(&ByAddress(words[3].clone())));
+
+        assert_eq!(format!("{:p}", words[0]), format!("{:p}", &*words[0]));
+
+        // Still works once the arena is gone.
+        drop(arena);
+        assert!(unique.contains(&ByAddress(words[3].clone())));
+    }
+
+    #[test]
+    fn uninit_slots() {
+        let counter = Rc::new(());
+        let arena: Arena<(usize, Rc<()>), 4> = Arena::new();
+
+        let a = arena.alloc((0, counter.clone()));
+        let pending = arena.alloc_uninit();
+        let b = arena.alloc((2, counter.clone()));
+        let later = pending.arena_ref();
+
+        // The slot is skipped until it holds a value.
+        assert!(later.try_get().is_none());
+        assert_eq!(arena.len(), 2);
+        assert_eq!(arena.iter_ref().map(|x| x.0).collect::<Vec<_>>(), [0, 2]);
+        assert!(arena.get(later.index()).is_none());
+
+        let one = pending.init((1, counter.clone()));
+        assert!(one.ptr_eq(&later));
+        assert_eq!(later.0, 1);
+        assert_eq!(arena.len(), 3);
+        assert_eq!(arena.iter_ref().map(|x| x.0).collect::<Vec<_>>(), [0, 1, 2]);
+
+        // A slot that is never filled goes back to the arena.
+        let dropped = arena.alloc_uninit();
+        let index = dropped.arena_ref().index();
+        drop(dropped);
+        assert!(arena.alloc((3, counter.clone())).index() == index);
+
+        let cyclic = arena.alloc_cyclic(|me| (me.index().into_raw() as usize, counter.clone()));
+        assert_eq!(cyclic.0, cyclic.index().into_raw() as usize);
+
+        // Resetting with a slot still set aside only drops the values, and the slot can't be
+        // filled anymore.
+        let pending = arena.alloc_uninit();
+        assert_eq!(Rc::strong_count(&counter), 6);
+
+        let mut arena = arena;
+        arena.reset();
+        assert_eq!(Rc::strong_count(&counter), 1);
+        assert!(pending.try_init((4, counter.clone())).is_err());
+        assert_eq!(Rc::strong_count(&counter), 1);
+
+        let pending = arena.alloc_uninit();
+        drop(arena);
+        assert!(pending.try_init((5, counter.clone())).is_err());
+    }
+
+    #[test]
+    fn init_while_iterating() {
+        let arena: Arena<i32, 4> = Arena::new();
+        arena.alloc(0);
+        let pending = arena.alloc_uninit();
+        arena.alloc_extend([2, 3, 4, 5]);
+
+        let mut refs = arena.iter_ref();
+        let mut back = arena.iter().rev();
+        assert_eq!(refs.next(), Some(&0));
+        assert_eq!(back.next().map(|r| *r), Some(5));
+        pending.init(1);
+
+        // Both were created before the value was written, so they never get to see it.
+        assert_eq!(refs.len(), 4);
+        assert_eq!(back.len(), 4);
+        let after: Vec<_> = arena.iter_ref().copied().collect();
+        assert_eq!(after, [0, 1, 2, 3, 4, 5]);
+        assert_eq!(refs.copied().collect::<Vec<_>>(), [2, 3, 4, 5]);
+        assert_eq!(back.map(|r| *r).collect::<Vec<_>>(), [4, 3, 2, 0]);
+
+        // Once every iterator is gone, the slot is like any other.
+        assert!(arena.inner.late.borrow().is_empty());
+        assert_eq!(arena.iter().nth(1).map(|r| *r), Some(1));
+        assert_eq!(arena.iter_ref().nth_back(4), Some(&1));
+
+        // Slots set aside while iterating are past the end of every iterator already going, so
+        // they don't have to be listed.
+        for x in arena.iter() {
+            arena.alloc_cyclic(|_| *x * 10);
+        }
+        let mut iter = arena.iter_ref();
+        arena.alloc_cyclic(|_| 100);
+        assert!(arena.inner.late.borrow().is_empty());
+        assert_eq!(iter.nth(11), Some(&50));
+        assert_eq!(iter.next(), None);
+    }
+
+    #[test]
+    fn compact_packs_values() {
+        let counter = Rc::new(());
+        let mut arena: Arena<(usize, Rc<()>), 4> = Arena::new();
+        arena.set_segment_limit(Some(8));
+
+        let refs: Vec<_> = (0..20).map(|i| arena.alloc((i, counter.clone()))).collect();
+        for r in refs.iter().filter(|r| r.0 % 3 != 0) {
+            assert!(unsafe { arena.remove(r) });
+        }
+        let kept: Vec<_> = (0..20).step_by(3).collect();
+        let indexes: Vec<_> = refs.iter().map(ArenaRef::index).collect();
+        assert_eq!(arena.segment_count(), 5);
+
+        drop(refs);
+
+        let moved = arena.compact();
+        assert_eq!(arena.iter_ref().map(|x| x.0).collect::<Vec<_>>(), kept);
+        assert_eq!(Rc::strong_count(&counter), 1 + kept.len());
+        assert_eq!((arena.segment_count(), arena.capacity()), (1, 8));
+        assert_eq!(arena.inner.segment_limit.get(), Some(8));
+
+        // Every old index leads to the same value.
+        assert_eq!(moved.len(), kept.len());
+        for &i in &kept {
+            assert_eq!(arena.get(moved[&indexes[i]]).unwrap().0, i);
+        }
+
+        // The arena carries on as usual.
+        let next = arena.alloc((20, counter.clone()));
+        assert_eq!(next.index().into_raw() as usize, kept.len());
+
+        let mut empty: Arena<u8, 4> = Arena::new();
+        assert!(empty.compact().is_empty());
+        assert_eq!(empty.capacity(), 4);
+    }
+
+    #[test]
+    #[should_panic(expected = "still shared")]
+    fn compact_shared_arena() {
+        let mut arena: Arena<i32> = Arena::new();
+        let other = arena.clone();
+        arena.compact();
+    }
+
+    #[test]
+    #[should_panic(expected = "still shared or referenced")]
+    fn compact_referenced_arena() {
+        let mut arena: Arena<i32> = Arena::new();
+        let r = arena.alloc(1);
+        arena.compact();
+    }
+
+    #[test]
+    fn alloc_ref_borrows() {
+        let arena: Arena<Cell<i32>, 4> = Arena::new();
+
+        let refs: Vec<&Cell<i32>> = (0..10).map(|i| arena.alloc_ref(Cell::new(i))).collect();
+        refs[3].set(-3);
+
+        // Borrowed and counted allocations live side by side.
+        let counted = arena.alloc(Cell::new(10));
+        assert_eq!(counted.get(), 10);
+
+        let lhs: Vec<i32> = arena.iter().map(|x| x.get()).collect();
+        assert_eq!(lhs, [0, 1, 2, -3, 4, 5, 6, 7, 8, 9, 10]);
+        assert_eq!(refs.iter().map(|x| x.get()).sum::<i32>(), 39);
+    }
+
+    #[test]
+    fn slices_are_contiguous() {
+        let arena: Arena<u32, 8> = Arena::new();
+
+        for i in 0..5 {
+            arena.alloc(i);
+        }
+
+        // Does not fit in what is left of the first segment.
+        let small = arena.alloc_slice_copy(&[5, 6, 7, 8, 9]);
+        assert_eq!(*small, [5, 6, 7, 8, 9]);
+
+        // Longer than a segment.
+        let big = arena.alloc_extend(10..30);
+        assert_eq!(*big, (10..30).collect::<Vec<_>>());
+
+        let empty = arena.alloc_slice_copy(&[]);
+        assert!(empty.is_empty());
+
+        arena.alloc(30);
+
+        let lhs: Vec<u32> = arena.iter().map(|x| *x).collect();
+        assert_eq!(lhs, (0..31).collect::<Vec<_>>());
+
+        let r = big.get_ref(3).unwrap();
+        assert_eq!(*r, 13);
+        assert!(big.get_ref(20).is_none());
+
+        drop(arena);
+        assert!(small.try_get().is_none());
+    }
+
+    #[test]
+    fn slices_drop_values() {
+        let counter = Rc::new(());
+        let arena: Arena<Rc<()>, 4> = Arena::new();
+
+        arena.alloc_slice_clone(&vec![counter.clone(); 10]);
+        arena.alloc_extend((0..3).map(|_| counter.clone()));
+        assert_eq!(Rc::strong_count(&counter), 14);
+
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn try_alloc_limits() {
+        let arena: Arena<String, 4> = Arena::new();
+        arena.set_segment_limit(Some(2));
+
+        for i in 0..8 {
+            arena.try_alloc(i.to_string()).unwrap();
+        }
+
+        let err = arena.try_alloc("8".to_string()).unwrap_err();
+        assert_eq!(err.kind(), AllocErrorKind::LimitReached);
+        assert_eq!(err.into_inner(), "8");
+
+        // Lifting the limit makes room again.
+        arena.set_segment_limit(None);
+        assert_eq!(*arena.try_alloc("8".to_string()).unwrap(), "8");
+
+        // Not even one more segment fits.
+        arena.set_byte_limit(Some(1));
+        for i in 9..12 {
+            arena.try_alloc(i.to_string()).unwrap();
+        }
+        assert!(arena.try_alloc("12".to_string()).is_err());
+        assert_eq!(arena.iter().count(), 12);
+    }
+
+    #[test]
+    #[should_panic(expected = "allocation limit")]
+    fn alloc_panics_past_limit() {
+        let arena: Arena<u8, 4> = Arena::new();
+        arena.set_segment_limit(Some(1));
+
+        arena.alloc_slice_copy(&[0; 5]);
+    }
+
+    #[test]
+    fn stats() {
+        let arena: Arena<u64, 8> = Arena::new();
+        assert!(arena.is_empty());
+        assert_eq!(arena.segment_count(), 1);
+        assert_eq!(arena.capacity(), 8);
+
+        for i in 0..10 {
+            arena.alloc(i);
+        }
+        arena.alloc_slice_copy(&[0; 20]);
+
+        let stats = arena.stats();
+        assert_eq!(stats.len, 30);
+        assert_eq!(stats.capacity, 8 + 8 + 20);
+        assert_eq!(stats.segment_count, 3);
+        assert!(stats.allocated_bytes >= 36 * std::mem::size_of::<u64>());
+        assert_eq!(stats.len, arena.iter().count());
+    }
+
+    #[test]
+    fn reserve() {
+        let arena: Arena<u32, 8> = Arena::with_capacity(20);
+        assert_eq!(arena.capacity(), 24);
+        assert_eq!(arena.segment_count(), 1);
+
+        for i in 0..20 {
+            arena.alloc(i);
+        }
+
+        arena.reserve(30);
+        let stats = arena.stats();
+        assert!(stats.capacity - stats.len >= 30);
+
+        // Already reserved, so this is a no-op.
+        arena.reserve(30);
+        assert_eq!(arena.stats(), stats);
+
+        for i in 20..50 {
+            arena.alloc(i);
+        }
+        assert_eq!(arena.segment_count(), stats.segment_count);
+
+        // A run bigger than a spare segment goes before it, keeping the spare for later.
+        arena.reserve(8);
+        let big = arena.alloc_extend(50..70);
+        let segments = arena.segment_count();
+        for i in 70..78 {
+            arena.alloc(i);
+        }
+        assert_eq!(arena.segment_count(), segments);
+
+        assert_eq!(*big, (50..70).collect::<Vec<_>>());
+        let lhs: Vec<u32> = arena.iter().map(|x| *x).collect();
+        assert_eq!(lhs, (0..78).collect::<Vec<_>>());
+
+        // Rounding up to a whole segment can't wrap around.
+        assert_eq!(
+            arena.try_reserve(usize::MAX),
+            Err(AllocErrorKind::OutOfMemory)
+        );
+        assert_eq!(arena.stats().len, 78);
+    }
+
+    #[test]
+    #[should_panic(expected = "too large")]
+    fn with_capacity_overflow() {
+        Arena::<u32, 8>::with_capacity(usize::MAX);
+    }
+
+    #[test]
+    fn indexes() {
+        let arena: Arena<u32, 4> = Arena::new();
+
+        let refs: Vec<_> = (0..10).map(|i| arena.alloc(i)).collect();
+        let slice = arena.alloc_slice_copy(&[10, 11, 12]);
+        let last = arena.alloc(13);
+
+        // Plain segments are numbered `segment * N + slot`.
+        assert_eq!(refs[6].index().into_raw(), 6);
+        assert_eq!(slice.get_ref(0).unwrap().index().into_raw(), 12);
+        assert_eq!(last.index().into_raw(), 15);
+
+        for r in refs.iter().chain([&last]) {
+            assert_eq!(arena.get(r.index()), Some(&**r));
+            assert!(arena.get_ref(r.index()).unwrap().ptr_eq(r));
+        }
+
+        // Left over at the end of a segment, and past the end.
+        assert_eq!(arena.get(ArenaIndex::from_raw(10)), None);
+        assert_eq!(arena.get(ArenaIndex::from_raw(16)), None);
+        assert_eq!(arena.get(ArenaIndex::from_raw(1000)), None);
+
+        let r = arena.alloc_rc(14);
+        assert_eq!(r.index(), ArenaIndex::from_raw(16));
+    }
+
+    #[test]
+    fn arena_rc_keeps_arena_alive() {
+        let arena: Arena<String, 4> = Arena::new();
+        let weak = arena.alloc("weak".to_string());
+        let strong = arena.alloc_rc("strong".to_string());
+
+        let upgraded = weak.upgrade().unwrap();
+        drop(arena);
 
+        // Both ArenaRcs keep the arena, and therefore every value in it, alive.
+        assert_eq!(&*strong, "strong");
+        assert_eq!(weak.try_get().map(String::as_str), Some("weak"));
+        assert!(upgraded.downgrade().ptr_eq(&weak));
 
+        drop(strong);
+        drop(upgraded);
 
+        assert_eq!(weak.try_get(), None);
+        assert!(weak.upgrade().is_none());
     }
 }
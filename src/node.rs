@@ -0,0 +1,575 @@
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    marker::PhantomData,
+    rc::Rc,
+};
+
+use crate::{Arena, ArenaIndex, ArenaRef, ArenaUninit};
+
+/// A value that holds [`ArenaRef`]s to other values of its own [`crate::Arena`], like the nodes of
+/// a graph.
+///
+/// Graph utilities only go through this trait, so they work on any node type. With the `derive`
+/// feature it can be derived, visiting every field through [`ArenaField`]. Fields marked
+/// `#[arena(skip)]` are left alone, and cloned as they are.
+///
+/// Serializing doesn't go through this trait. With the `serde` feature an [`ArenaRef`] writes
+/// itself as the position of its value and is pointed back at it when read, so deriving
+/// `Serialize` and `Deserialize` on the node type is all it takes.
+///
+/// # Safety
+///
+/// [`ArenaNode::for_each_ref`] must only call `f` with [`ArenaRef`]s owned by this value, and
+/// never twice with the same one. [`Arena::compact_nodes`] counts them to make sure no handle
+/// outside of the values is left to dangle. Leaving one out is fine, it only makes compacting
+/// refuse.
+///
+/// Example:
+/// ```
+/// use light_rc_arena::*;
+///
+/// struct Node {
+///     name: String,
+///     next: Option<ArenaRef<Node, 64>>,
+/// }
+///
+/// // What `#[derive(ArenaNode)]` generates, give or take.
+/// unsafe impl ArenaNode<64> for Node {
+///     fn for_each_ref(&self, f: &mut dyn FnMut(&ArenaRef<Self, 64>)) {
+///         self.next.for_each_field_ref(f);
+///     }
+///
+///     fn clone_remapped(
+///         &self,
+///         map: &mut dyn FnMut(&ArenaRef<Self, 64>) -> ArenaRef<Self, 64>,
+///     ) -> Self {
+///         Node {
+///             name: self.name.clone(),
+///             next: self.next.clone_field_remapped(map),
+///         }
+///     }
+///
+///     fn remap_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<Self, 64>) -> ArenaRef<Self, 64>) {
+///         self.next.remap_field_refs(map);
+///     }
+/// }
+///
+/// let arena = Arena::<Node>::new();
+/// let a = arena.alloc(Node { name: "a".into(), next: None });
+/// let b = arena.alloc(Node { name: "b".into(), next: Some(a.clone()) });
+///
+/// let mut children = Vec::new();
+/// b.for_each_ref(&mut |child| children.push(child.name.clone()));
+/// assert_eq!(children, ["a"]);
+/// ```
+pub unsafe trait ArenaNode<const N: usize>: Sized {
+    /// Call `f` with every [`ArenaRef`] this value holds, in field order.
+    fn for_each_ref(&self, f: &mut dyn FnMut(&ArenaRef<Self, N>));
+
+    /// Clone this value, replacing every [`ArenaRef`] it holds with the one `map` returns for
+    /// it. This is how a value is moved over to another arena.
+    fn clone_remapped(&self, map: &mut dyn FnMut(&ArenaRef<Self, N>) -> ArenaRef<Self, N>) -> Self;
+
+    /// Replace every [`ArenaRef`] this value holds with the one `map` returns for it, in place.
+    fn remap_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<Self, N>) -> ArenaRef<Self, N>);
+}
+
+/// A field of an [`ArenaNode`] of type `T`, that may hold [`ArenaRef`]s to other nodes.
+///
+/// Implemented for [`ArenaRef`] itself, containers of fields, and common types that hold no
+/// references at all.
+///
+/// # Safety
+///
+/// Same as [`ArenaNode`], [`ArenaField::for_each_field_ref`] must only call `f` with
+/// [`ArenaRef`]s owned by this field, and never twice with the same one.
+pub unsafe trait ArenaField<T, const N: usize>: Sized {
+    /// See [`ArenaNode::for_each_ref`].
+    fn for_each_field_ref(&self, f: &mut dyn FnMut(&ArenaRef<T, N>));
+
+    /// See [`ArenaNode::clone_remapped`].
+    fn clone_field_remapped(&self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) -> Self;
+
+    /// See [`ArenaNode::remap_refs`].
+    fn remap_field_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>);
+}
+
+unsafe impl<T, const N: usize> ArenaField<T, N> for ArenaRef<T, N> {
+    fn for_each_field_ref(&self, f: &mut dyn FnMut(&ArenaRef<T, N>)) {
+        f(self);
+    }
+
+    fn clone_field_remapped(&self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) -> Self {
+        map(self)
+    }
+
+    fn remap_field_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) {
+        *self = map(self);
+    }
+}
+
+unsafe impl<T, F: ArenaField<T, N>, const N: usize> ArenaField<T, N> for Option<F> {
+    fn for_each_field_ref(&self, f: &mut dyn FnMut(&ArenaRef<T, N>)) {
+        if let Some(field) = self {
+            field.for_each_field_ref(f);
+        }
+    }
+
+    fn clone_field_remapped(&self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) -> Self {
+        self.as_ref().map(|field| field.clone_field_remapped(map))
+    }
+
+    fn remap_field_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) {
+        if let Some(field) = self {
+            field.remap_field_refs(map);
+        }
+    }
+}
+
+unsafe impl<T, F: ArenaField<T, N>, const N: usize> ArenaField<T, N> for Vec<F> {
+    fn for_each_field_ref(&self, f: &mut dyn FnMut(&ArenaRef<T, N>)) {
+        self.iter().for_each(|field| field.for_each_field_ref(f));
+    }
+
+    fn clone_field_remapped(&self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) -> Self {
+        self.iter()
+            .map(|field| field.clone_field_remapped(map))
+            .collect()
+    }
+
+    fn remap_field_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) {
+        self.iter_mut()
+            .for_each(|field| field.remap_field_refs(map));
+    }
+}
+
+unsafe impl<T, F: ArenaField<T, N>, const N: usize, const K: usize> ArenaField<T, N> for [F; K] {
+    fn for_each_field_ref(&self, f: &mut dyn FnMut(&ArenaRef<T, N>)) {
+        self.iter().for_each(|field| field.for_each_field_ref(f));
+    }
+
+    fn clone_field_remapped(&self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) -> Self {
+        std::array::from_fn(|i| self[i].clone_field_remapped(map))
+    }
+
+    fn remap_field_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) {
+        self.iter_mut()
+            .for_each(|field| field.remap_field_refs(map));
+    }
+}
+
+unsafe impl<T, F: ArenaField<T, N>, const N: usize> ArenaField<T, N> for Box<F> {
+    fn for_each_field_ref(&self, f: &mut dyn FnMut(&ArenaRef<T, N>)) {
+        (**self).for_each_field_ref(f);
+    }
+
+    fn clone_field_remapped(&self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) -> Self {
+        Box::new((**self).clone_field_remapped(map))
+    }
+
+    fn remap_field_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) {
+        (**self).remap_field_refs(map);
+    }
+}
+
+/// Values in an arena are shared, so a [`RefCell`] is how their references get rewired.
+///
+/// # Panics
+///
+/// Panics if the cell is already mutably borrowed.
+unsafe impl<T, F: ArenaField<T, N>, const N: usize> ArenaField<T, N> for RefCell<F> {
+    fn for_each_field_ref(&self, f: &mut dyn FnMut(&ArenaRef<T, N>)) {
+        self.borrow().for_each_field_ref(f);
+    }
+
+    fn clone_field_remapped(&self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) -> Self {
+        RefCell::new(self.borrow().clone_field_remapped(map))
+    }
+
+    fn remap_field_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) {
+        self.get_mut().remap_field_refs(map);
+    }
+}
+
+// Types that can't hold an `ArenaRef`, so they are just cloned.
+macro_rules! leaf_fields {
+    ($([$($generics:tt)*] $ty:ty),* $(,)?) => {
+        $(
+            unsafe impl<T, $($generics)* const N: usize> ArenaField<T, N> for $ty {
+                fn for_each_field_ref(&self, _: &mut dyn FnMut(&ArenaRef<T, N>)) {}
+
+                fn clone_field_remapped(
+                    &self,
+                    _: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>,
+                ) -> Self {
+                    self.clone()
+                }
+
+                fn remap_field_refs(&mut self, _: &mut dyn FnMut(&ArenaRef<T, N>) -> ArenaRef<T, N>) {}
+            }
+        )*
+    };
+}
+
+leaf_fields!(
+    [] (), [] bool, [] char, [] String, [] &'static str,
+    [] u8, [] u16, [] u32, [] u64, [] u128, [] usize,
+    [] i8, [] i16, [] i32, [] i64, [] i128, [] isize,
+    [] f32, [] f64,
+    [C: Copy,] Cell<C>, [U,] ArenaIndex<U>, [U: ?Sized,] PhantomData<U>,
+);
+
+impl<T: ArenaNode<N>, const N: usize> Arena<T, N> {
+    /// Deep copy every value of this arena into `other`, unlike [`Clone`] which only clones the
+    /// handle. [`ArenaRef`]s between values are rewired to point to their copies, while refs into
+    /// any other arena are kept as they are.
+    ///
+    /// Returns the copy of every value, by the [`ArenaIndex`] of the original.
+    ///
+    /// Example:
+    /// ```
+    /// use light_rc_arena::*;
+    /// use std::cell::Cell;
+    ///
+    /// struct Counter {
+    ///     count: Cell<u32>,
+    ///     next: Option<ArenaRef<Counter, 64>>,
+    /// }
+    ///
+    /// # unsafe impl ArenaNode<64> for Counter {
+    /// #     fn for_each_ref(&self, f: &mut dyn FnMut(&ArenaRef<Self, 64>)) {
+    /// #         self.next.for_each_field_ref(f);
+    /// #     }
+    /// #     fn clone_remapped(
+    /// #         &self,
+    /// #         map: &mut dyn FnMut(&ArenaRef<Self, 64>) -> ArenaRef<Self, 64>,
+    /// #     ) -> Self {
+    /// #         Counter {
+    /// #             count: self.count.clone(),
+    /// #             next: self.next.clone_field_remapped(map),
+    /// #         }
+    /// #     }
+    /// #     fn remap_refs(&mut self, map: &mut dyn FnMut(&ArenaRef<Self, 64>) -> ArenaRef<Self, 64>) {
+    /// #         self.next.remap_field_refs(map);
+    /// #     }
+    /// # }
+    /// let arena = Arena::<Counter>::new();
+    /// let last = arena.alloc(Counter { count: Cell::new(0), next: None });
+    /// let first = arena.alloc(Counter { count: Cell::new(0), next: Some(last.clone()) });
+    ///
+    /// // Take a snapshot, then change the original.
+    /// let snapshot = Arena::new();
+    /// let copies = arena.clone_into(&snapshot);
+    /// last.count.set(10);
+    ///
+    /// let first_copy = &copies[&first.index()];
+    /// assert_eq!(first_copy.next.as_ref().unwrap().count.get(), 0);
+    /// assert!(first_copy.next.as_ref().unwrap().ptr_eq(&copies[&last.index()]));
+    /// ```
+    pub fn clone_into(&self, other: &Arena<T, N>) -> HashMap<ArenaIndex<T>, ArenaRef<T, N>> {
+        let originals: Vec<ArenaRef<T, N>> = self.iter().collect();
+
+        // Every copy gets its slot up front, so values can point to copies not made yet.
+        let slots: Vec<ArenaUninit<T, N>> =
+            originals.iter().map(|_| other.alloc_uninit()).collect();
+        let copies: Vec<ArenaRef<T, N>> = slots.iter().map(ArenaUninit::arena_ref).collect();
+
+        let arena = Rc::downgrade(&self.inner);
+        let mut remap = |r: &ArenaRef<T, N>| {
+            let position = r
+                .arena
+                .ptr_eq(&arena)
+                .then(|| originals.binary_search_by_key(&r.index, |original| original.index))
+                .and_then(Result::ok)
+                .filter(|&position| originals[position].ptr_eq(r));
+
+            match position {
+                Some(position) => copies[position].clone(),
+                None => r.clone(),
+            }
+        };
+
+        for (original, slot) in originals.iter().zip(slots) {
+            slot.init(original.clone_remapped(&mut remap));
+        }
+
+        originals
+            .iter()
+            .map(ArenaRef::index)
+            .zip(copies.iter().cloned())
+            .collect()
+    }
+
+    /// Like [`Arena::compact`], but rewires the [`ArenaRef`]s values hold to each other so they
+    /// keep pointing to the same values, while refs into any other arena are kept as they are.
+    ///
+    /// Returns a ref to every value, by the [`ArenaIndex`] it had before, like
+    /// [`Arena::clone_into`]. If [`ArenaNode::remap_refs`] panics, the values are dropped,
+    /// leaving the arena empty.
+    ///
+    /// Unlike [`Arena::compact`], the values may hold [`ArenaRef`]s to each other. Any other
+    /// handle into the arena could have a `&T` read out of it, which would dangle once the values
+    /// move, so there can't be any.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the arena is shared, see [`Arena::is_unique`], or if there are [`ArenaRef`]s
+    /// (or [`ArenaSlice`](crate::ArenaSlice)s, [`ArenaUninit`]s) into it other than the ones its
+    /// values hold.
+    pub fn compact_nodes(&mut self) -> HashMap<ArenaIndex<T>, ArenaRef<T, N>> {
+        assert!(
+            self.is_unique(),
+            "Can't compact an arena that is still shared!"
+        );
+
+        let arena = Rc::downgrade(&self.inner);
+        let mut internal = 0;
+        for value in self.iter_ref() {
+            value.for_each_ref(&mut |r| internal += r.arena.ptr_eq(&arena) as usize);
+        }
+        drop(arena);
+
+        assert!(
+            Rc::weak_count(&self.inner) == internal,
+            "Can't compact an arena that is still referenced from outside of its values!"
+        );
+
+        let moved = self.compact_with(|value, map| value.remap_refs(map));
+
+        moved
+            .into_iter()
+            .enumerate()
+            .map(|(new, old)| {
+                let r = self.get_ref(ArenaIndex::from_usize(new)).unwrap();
+                (ArenaIndex::from_usize(old), r)
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Arena;
+    use light_rc_arena_derive::ArenaNode;
+
+    #[derive(ArenaNode)]
+    enum Expr {
+        Const(i64),
+        Add(ArenaRef<Expr, 8>, ArenaRef<Expr, 8>),
+        Call {
+            name: String,
+            args: Vec<ArenaRef<Expr, 8>>,
+            #[arena(skip)]
+            span: std::ops::Range<usize>,
+        },
+        Hole,
+    }
+
+    // Shapes with nothing to visit derive too.
+    #[derive(ArenaNode)]
+    enum Never {}
+
+    #[derive(ArenaNode)]
+    struct Leaf;
+
+    #[derive(ArenaNode)]
+    struct Cons<X: Clone> {
+        #[arena(skip)]
+        head: X,
+        tail: RefCell<Option<ArenaRef<Cons<X>, 4>>>,
+    }
+
+    #[test]
+    fn derived_nodes() {
+        let arena: Arena<Expr, 8> = Arena::new();
+
+        let one = arena.alloc(Expr::Const(1));
+        let two = arena.alloc(Expr::Const(2));
+        let sum = arena.alloc(Expr::Add(one.clone(), two.clone()));
+        let call = arena.alloc(Expr::Call {
+            name: "f".to_string(),
+            args: vec![sum.clone(), one.clone()],
+            span: 3..7,
+        });
+
+        let children = |node: &Expr| {
+            let mut children = Vec::new();
+            node.for_each_ref(&mut |child| children.push(child.clone()));
+            children
+        };
+
+        assert!(children(&one).is_empty());
+        assert!(children(&Expr::Hole).is_empty());
+        assert!(children(&sum)[1].ptr_eq(&two));
+        assert_eq!(children(&call).len(), 2);
+
+        // Every ref to `one` now points to `two`.
+        let mut swap = |r: &ArenaRef<Expr, 8>| {
+            if r.ptr_eq(&one) {
+                two.clone()
+            } else {
+                r.clone()
+            }
+        };
+
+        let Expr::Call { name, args, span } = call.clone_remapped(&mut swap) else {
+            unreachable!()
+        };
+        assert_eq!((name.as_str(), span), ("f", 3..7));
+        assert!(args[0].ptr_eq(&sum) && args[1].ptr_eq(&two));
+
+        let mut sum = Expr::Add(one.clone(), one.clone());
+        sum.remap_refs(&mut swap);
+        assert!(children(&sum).iter().all(|child| child.ptr_eq(&two)));
+    }
+
+    #[test]
+    fn derived_generic_nodes() {
+        let arena: Arena<Cons<&str>, 4> = Arena::new();
+
+        let tail = arena.alloc(Cons {
+            head: "b",
+            tail: RefCell::new(None),
+        });
+        let list = arena.alloc(Cons {
+            head: "a",
+            tail: RefCell::new(Some(tail.clone())),
+        });
+
+        // Close the loop through the cell.
+        *tail.tail.borrow_mut() = Some(list.clone());
+
+        let mut visited = Vec::new();
+        list.for_each_ref(&mut |next| visited.push(next.head));
+        tail.for_each_ref(&mut |next| visited.push(next.head));
+        assert_eq!(visited, ["b", "a"]);
+
+        let copy = list.clone_remapped(&mut |r| r.clone());
+        assert_eq!(copy.head, "a");
+        assert!(copy.tail.borrow().as_ref().unwrap().ptr_eq(&tail));
+    }
+
+    #[test]
+    fn clone_into_rewires_refs() {
+        let arena: Arena<Cons<u32>, 4> = Arena::new();
+        let foreign: Arena<Cons<u32>, 4> = Arena::new();
+        let outside = foreign.alloc(Cons {
+            head: 99,
+            tail: RefCell::new(None),
+        });
+
+        // A ring of 6, with the value after the first removed, and the last one pointing out.
+        let mut nodes: Vec<_> = (0..7)
+            .map(|head| {
+                arena.alloc(Cons {
+                    head,
+                    tail: RefCell::new(None),
+                })
+            })
+            .collect();
+        let mut arena = arena;
+        assert!(unsafe { arena.remove(&nodes.remove(1)) });
+
+        for (node, next) in nodes.iter().zip(nodes.iter().skip(1)) {
+            *node.tail.borrow_mut() = Some(next.clone());
+        }
+        *nodes[5].tail.borrow_mut() = Some(outside.clone());
+
+        let snapshot = Arena::new();
+        let copies = arena.clone_into(&snapshot);
+        assert_eq!((copies.len(), snapshot.len()), (6, 6));
+
+        // Changing the original leaves the copy alone.
+        *nodes[0].tail.borrow_mut() = None;
+
+        let mut node = copies[&nodes[0].index()].clone();
+        let mut heads = vec![node.head];
+        loop {
+            let next = node.tail.borrow().clone().unwrap();
+            heads.push(next.head);
+            if next.get_arena().is_some_and(|arena| arena != snapshot) {
+                break;
+            }
+            node = next;
+        }
+
+        assert_eq!(heads, [0, 2, 3, 4, 5, 6, 99]);
+        assert!(
+            copies[&nodes[5].index()]
+                .tail
+                .borrow()
+                .as_ref()
+                .unwrap()
+                .ptr_eq(&outside)
+        );
+    }
+
+    #[test]
+    fn compact_nodes_rewires_refs() {
+        let mut arena: Arena<Cons<u32>, 4> = Arena::new();
+        let foreign: Arena<Cons<u32>, 4> = Arena::new();
+        let outside = foreign.alloc(Cons {
+            head: 99,
+            tail: RefCell::new(None),
+        });
+
+        // Every other value is removed, and the rest form a ring with one ref pointing out.
+        let nodes: Vec<_> = (0..12)
+            .map(|head| {
+                arena.alloc(Cons {
+                    head,
+                    tail: RefCell::new(Some(outside.clone())),
+                })
+            })
+            .collect();
+        let (kept, removed): (Vec<_>, Vec<_>) = nodes.into_iter().partition(|n| n.head % 2 == 0);
+        for node in &removed {
+            assert!(unsafe { arena.remove(node) });
+        }
+        for (node, next) in kept.iter().zip(kept.iter().skip(1).chain(&kept[..1])) {
+            *node.tail.borrow_mut() = Some(next.clone());
+        }
+        *kept[5].tail.borrow_mut() = Some(outside.clone());
+
+        // Only the refs the values hold to each other may be left.
+        let first = kept[0].index();
+        drop((kept, removed));
+
+        let moved = arena.compact_nodes();
+        assert_eq!(arena.segment_count(), 1);
+
+        let mut node = moved[&first].clone();
+        let mut heads = vec![node.head];
+        while node.head != 10 {
+            let next = node.tail.borrow().clone().unwrap();
+            heads.push(next.head);
+            node = next;
+        }
+
+        assert_eq!(heads, [0, 2, 4, 6, 8, 10]);
+        assert!(node.get_arena().unwrap() == arena);
+        assert!(node.tail.borrow().as_ref().unwrap().ptr_eq(&outside));
+    }
+
+    #[test]
+    #[should_panic(expected = "referenced from outside of its values")]
+    fn compact_nodes_referenced_arena() {
+        let mut arena: Arena<Cons<u32>, 4> = Arena::new();
+        let a = arena.alloc(Cons {
+            head: 0,
+            tail: RefCell::new(None),
+        });
+        let b = arena.alloc(Cons {
+            head: 1,
+            tail: RefCell::new(Some(a.clone())),
+        });
+        *a.tail.borrow_mut() = Some(b.clone());
+        drop(a);
+
+        let head: &u32 = &b.head;
+        arena.compact_nodes();
+        assert_eq!(*head, 1);
+    }
+}
@@ -0,0 +1,364 @@
+use std::{any::Any, cell::RefCell, marker::PhantomData, rc::Rc};
+
+use serde::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{self, SeqAccess, Visitor},
+    ser::{self, SerializeSeq, SerializeTuple},
+};
+
+use crate::{Arena, ArenaInner, ArenaRef};
+
+// The arenas being (de)serialized on this thread, innermost last. `ArenaRef`s look themselves up
+// here, as serde gives no other way to pass state down to them.
+thread_local! {
+    static SERIALIZING: RefCell<Vec<Serializing>> = const { RefCell::new(Vec::new()) };
+    static DESERIALIZING: RefCell<Vec<Box<dyn Any>>> = const { RefCell::new(Vec::new()) };
+}
+
+struct Serializing {
+    arena: *const (),
+
+    // Index of every value written out, in order. A ref is encoded as its position in here.
+    indexes: Vec<u32>,
+}
+
+struct Deserializing<T, const N: usize> {
+    inner: Rc<ArenaInner<T, N>>,
+    len: usize,
+}
+
+// Pop the arena pushed by `Arena::serialize` or `Arena::deserialize`, even when unwinding.
+struct PopSerializing;
+struct PopDeserializing;
+
+impl Drop for PopSerializing {
+    fn drop(&mut self) {
+        SERIALIZING.with_borrow_mut(|stack| stack.pop());
+    }
+}
+
+impl Drop for PopDeserializing {
+    fn drop(&mut self) {
+        DESERIALIZING.with_borrow_mut(|stack| stack.pop());
+    }
+}
+
+/// Written as a pair of the number of values and the values themselves, in order.
+///
+/// Every [`ArenaRef`] into this arena that a value holds is written as the position of the value
+/// it points to. Serializing a ref to a value of any other arena, or one that is no longer
+/// alive, is an error.
+///
+/// So refs only make the round trip within the values of their own arena. One held anywhere
+/// else, even right next to the arena, can't be written out. Keep the position of its value
+/// instead, which is its [`ArenaIndex`](crate::ArenaIndex) in the arena once read back.
+impl<T: Serialize, const N: usize> Serialize for Arena<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let refs: Vec<ArenaRef<T, N>> = self.iter().collect();
+
+        SERIALIZING.with_borrow_mut(|stack| {
+            stack.push(Serializing {
+                arena: Rc::as_ptr(&self.inner).cast(),
+                indexes: refs.iter().map(|r| r.index).collect(),
+            })
+        });
+        let _pop = PopSerializing;
+
+        let mut tuple = serializer.serialize_tuple(2)?;
+        tuple.serialize_element(&refs.len())?;
+        tuple.serialize_element(&Values(&refs))?;
+        tuple.end()
+    }
+}
+
+struct Values<'a, T, const N: usize>(&'a [ArenaRef<T, N>]);
+
+impl<T: Serialize, const N: usize> Serialize for Values<'_, T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(self.0.len()))?;
+        for r in self.0 {
+            seq.serialize_element(&**r)?;
+        }
+        seq.end()
+    }
+}
+
+/// Written as the position of the value it points to, see the [`Serialize`] impl of [`Arena`].
+///
+/// It can only be serialized as a part of the [`Arena`] it points into.
+impl<T, const N: usize> Serialize for ArenaRef<T, N> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if !self.is_live() {
+            return Err(ser::Error::custom(
+                "ArenaRef points to a value that is no longer alive",
+            ));
+        }
+
+        let arena = self.arena.as_ptr().cast();
+        let position = SERIALIZING.with_borrow(|stack| {
+            let serializing = stack.iter().rev().find(|s| s.arena == arena)?;
+            serializing.indexes.binary_search(&self.index).ok()
+        });
+
+        match position {
+            Some(position) => serializer.serialize_u64(position as u64),
+            None => Err(ser::Error::custom(
+                "ArenaRef points outside of the arena being serialized",
+            )),
+        }
+    }
+}
+
+/// Rebuilds the arena, pointing every [`ArenaRef`] held by its values back into it. Refs may
+/// point to values further along, so cycles survive the round trip.
+impl<'de, T: Deserialize<'de> + 'static, const N: usize> Deserialize<'de> for Arena<T, N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_tuple(2, ArenaVisitor(PhantomData))
+    }
+}
+
+struct ArenaVisitor<T, const N: usize>(PhantomData<fn() -> T>);
+
+impl<'de, T: Deserialize<'de> + 'static, const N: usize> Visitor<'de> for ArenaVisitor<T, N> {
+    type Value = Arena<T, N>;
+
+    fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("an arena")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let len: usize = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+
+        // `len` can't be trusted until the values are read, so nothing is set aside before that.
+        // Refs only hold the position of their value, which is the index it gets once the values
+        // are spread over the segments in order.
+        let arena = Arena::new();
+        let first = arena.inner.segment(0).unwrap();
+
+        let values: Vec<T> = {
+            DESERIALIZING.with_borrow_mut(|stack| {
+                stack.push(Box::new(Deserializing {
+                    inner: arena.inner.clone(),
+                    len,
+                }))
+            });
+            let _pop = PopDeserializing;
+
+            let values: Vec<T> = seq
+                .next_element()?
+                .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+            if values.len() != len {
+                return Err(de::Error::invalid_length(
+                    values.len(),
+                    &"as many values as declared",
+                ));
+            }
+
+            // A value could have gotten hold of the arena through one of its refs. Anything it
+            // allocated either went into the first segment, or into a segment of its own.
+            if !arena.is_empty() || arena.segment_count() != 1 || first.length.get() != 0 {
+                return Err(de::Error::custom(
+                    "arena was changed while being deserialized",
+                ));
+            }
+
+            values
+        };
+
+        arena.inner.alloc_spread(values, |_| ());
+        Ok(arena)
+    }
+}
+
+/// Read back as a ref to the value at that position of the [`Arena`] being deserialized, see
+/// the [`Deserialize`] impl of [`Arena`].
+///
+/// The value may not have been read yet, in which case the ref can't be used until the
+/// [`Arena`] is done. Outside of the values of an [`Arena`] it can't be read at all.
+impl<'de, T: 'static, const N: usize> Deserialize<'de> for ArenaRef<T, N> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let position = u64::deserialize(deserializer)?;
+
+        DESERIALIZING.with_borrow(|stack| {
+            let deserializing = stack
+                .iter()
+                .rev()
+                .find_map(|d| d.downcast_ref::<Deserializing<T, N>>())
+                .ok_or_else(|| {
+                    de::Error::custom("ArenaRef can only be deserialized inside of its arena")
+                })?;
+
+            let position = u32::try_from(position)
+                .ok()
+                .map(|position| position as usize)
+                .filter(|&position| position < deserializing.len)
+                .ok_or_else(|| {
+                    de::Error::invalid_value(
+                        de::Unexpected::Unsigned(position),
+                        &"the position of a value in the arena",
+                    )
+                })?;
+
+            Ok(ArenaRef {
+                arena: Rc::downgrade(&deserializing.inner),
+                generation: deserializing.inner.generation.get(),
+
+                // The stamp the slot will have once the value is moved in by `alloc_spread`.
+                stamp: 1,
+                index: position as u32,
+            })
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct Node {
+        name: String,
+        next: RefCell<Option<ArenaRef<Node, 4>>>,
+    }
+
+    #[test]
+    fn round_trip_with_cycles() {
+        let arena: Arena<Node, 4> = Arena::new();
+        let names = ["a", "b", "gone", "c", "d", "e"];
+        let nodes: Vec<_> = names
+            .iter()
+            .map(|&name| {
+                arena.alloc(Node {
+                    name: name.to_string(),
+                    next: RefCell::new(None),
+                })
+            })
+            .collect();
+
+        // a -> c -> e -> a, with a hole left behind by "gone".
+        *nodes[0].next.borrow_mut() = Some(nodes[3].clone());
+        *nodes[3].next.borrow_mut() = Some(nodes[5].clone());
+        *nodes[5].next.borrow_mut() = Some(nodes[0].clone());
+        *nodes[1].next.borrow_mut() = Some(nodes[1].clone());
+
+        let mut arena = arena;
+        assert!(unsafe { arena.remove(&nodes[2]) });
+
+        let json = serde_json::to_string(&arena).unwrap();
+        let copy: Arena<Node, 4> = serde_json::from_str(&json).unwrap();
+
+        let names: Vec<_> = copy.iter_ref().map(|node| node.name.as_str()).collect();
+        assert_eq!(names, ["a", "b", "c", "d", "e"]);
+
+        let a = copy.iter().next().unwrap();
+        let mut node = a.clone();
+        let mut visited = Vec::new();
+        for _ in 0..3 {
+            let next = node.next.borrow().clone().unwrap();
+            node = next;
+            visited.push(node.name.clone());
+        }
+        assert_eq!(visited, ["c", "e", "a"]);
+        assert!(node.ptr_eq(&a));
+        assert!(node.get_arena().unwrap() == copy);
+    }
+
+    #[test]
+    fn foreign_and_dead_refs() {
+        let arena: Arena<Node, 4> = Arena::new();
+        let other: Arena<Node, 4> = Arena::new();
+
+        let node = arena.alloc(Node {
+            name: "a".to_string(),
+            next: RefCell::new(None),
+        });
+        let foreign = other.alloc(Node {
+            name: "b".to_string(),
+            next: RefCell::new(None),
+        });
+        *node.next.borrow_mut() = Some(foreign);
+
+        let err = serde_json::to_string(&arena).unwrap_err();
+        assert!(err.to_string().contains("outside of the arena"));
+
+        // Serializing the other arena as a whole is fine, it has no refs.
+        assert!(serde_json::to_string(&other).is_ok());
+        drop(other);
+
+        let err = serde_json::to_string(&arena).unwrap_err();
+        assert!(err.to_string().contains("no longer alive"));
+
+        let err = serde_json::from_str::<Arena<Node, 4>>(r#"[1, [{"name": "a", "next": 1}]]"#)
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("position of a value"));
+
+        let err = serde_json::from_str::<ArenaRef<Node, 4>>("0")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("inside of its arena"));
+    }
+
+    // Allocates a run of copies of itself into the arena as soon as it is read.
+    struct Meddler(ArenaRef<Meddler, 4>);
+
+    impl<'de> Deserialize<'de> for Meddler {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let r: ArenaRef<Meddler, 4> = ArenaRef::deserialize(deserializer)?;
+            let arena = r.get_arena().unwrap();
+            arena.alloc_extend((0..8).map(|_| Meddler(r.clone())));
+            Ok(Meddler(r))
+        }
+    }
+
+    #[test]
+    fn round_trip_across_segments() {
+        let arena: Arena<Node, 4> = Arena::new();
+        let nodes: Vec<_> = (0..30)
+            .map(|i| {
+                arena.alloc(Node {
+                    name: i.to_string(),
+                    next: RefCell::new(None),
+                })
+            })
+            .collect();
+        for (i, node) in nodes.iter().enumerate() {
+            *node.next.borrow_mut() = Some(nodes[(i * 13 + 5) % 30].clone());
+        }
+
+        let json = serde_json::to_string(&arena).unwrap();
+        let copy: Arena<Node, 4> = serde_json::from_str(&json).unwrap();
+        assert_eq!(copy.len(), 30);
+        assert_eq!(copy.segment_count(), 8);
+
+        for (i, node) in copy.iter().enumerate() {
+            let next = node.next.borrow().clone().unwrap();
+            assert_eq!(next.name, ((i * 13 + 5) % 30).to_string());
+            assert!(next.get_arena().unwrap() == copy);
+        }
+    }
+
+    #[test]
+    fn untrusted_length() {
+        // Nothing is set aside up front, not even for the ref.
+        let json = r#"[1099511627776, [{"name": "a", "next": 4294967295}]]"#;
+        let err = serde_json::from_str::<Arena<Node, 4>>(json).err().unwrap();
+        assert!(err.to_string().contains("as many values as declared"));
+
+        let err = serde_json::from_str::<Arena<Node, 4>>("[50000000, []]")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("as many values as declared"));
+    }
+
+    #[test]
+    fn arena_changed_while_deserializing() {
+        let err = serde_json::from_str::<Arena<Meddler, 4>>("[1, [0]]")
+            .err()
+            .unwrap();
+        assert!(err.to_string().contains("changed while being deserialized"));
+    }
+}
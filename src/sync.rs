@@ -0,0 +1,394 @@
+use std::{
+    cell::UnsafeCell,
+    fmt::{Debug, Display, Formatter},
+    mem::MaybeUninit,
+    ops::Deref,
+    ptr::{addr_of_mut, null_mut},
+    sync::{
+        Arc, Mutex, Weak,
+        atomic::{AtomicPtr, AtomicUsize, Ordering},
+    },
+};
+
+struct SyncSegment<T, const N: usize> {
+    // Only ever grows. Slots below `length` are initialized and published with `Release`.
+    length: AtomicUsize,
+
+    // Owned by this segment once set, and never changed afterwards.
+    next: AtomicPtr<SyncSegment<T, N>>,
+
+    data: [UnsafeCell<MaybeUninit<T>>; N],
+}
+
+impl<T, const N: usize> SyncSegment<T, N> {
+    fn new() -> Box<SyncSegment<T, N>> {
+        // Build it directly on the heap, as a `[_; N]` this large could overflow the stack.
+        unsafe {
+            let layout = std::alloc::Layout::new::<SyncSegment<T, N>>();
+            let ptr = std::alloc::alloc(layout) as *mut SyncSegment<T, N>;
+
+            if ptr.is_null() {
+                std::alloc::handle_alloc_error(layout);
+            }
+
+            addr_of_mut!((*ptr).length).write(AtomicUsize::new(0));
+            addr_of_mut!((*ptr).next).write(AtomicPtr::new(null_mut()));
+
+            Box::from_raw(ptr)
+        }
+    }
+}
+
+impl<T, const N: usize> Drop for SyncSegment<T, N> {
+    fn drop(&mut self) {
+        unsafe {
+            for i in 0..*self.length.get_mut() {
+                self.data[i].get_mut().assume_init_drop();
+            }
+        }
+
+        // Unlink the chain iteratively so long arenas can't overflow the stack.
+        let mut next = *self.next.get_mut();
+        while !next.is_null() {
+            let mut segment = unsafe { Box::from_raw(next) };
+            next = std::mem::replace(segment.next.get_mut(), null_mut());
+        }
+    }
+}
+
+struct SyncArenaInner<T, const N: usize> {
+    // The lock only guards the bump, readers never take it.
+    tail: Mutex<*mut SyncSegment<T, N>>,
+
+    // Kept as a raw pointer (freed in `Drop`), as moving a `Box` would invalidate `tail`.
+    head: *mut SyncSegment<T, N>,
+}
+
+impl<T, const N: usize> Drop for SyncArenaInner<T, N> {
+    fn drop(&mut self) {
+        drop(unsafe { Box::from_raw(self.head) });
+    }
+}
+
+impl<T, const N: usize> SyncArenaInner<T, N> {
+    fn alloc(&self, cont: T) -> *const T {
+        let mut tail_guard = self.tail.lock().unwrap_or_else(|err| err.into_inner());
+        let mut tail = unsafe { &**tail_guard };
+
+        // Only the lock holder writes `length`, so a relaxed load is enough here.
+        let mut old_length = tail.length.load(Ordering::Relaxed);
+        if old_length >= N {
+            let segment = Box::into_raw(SyncSegment::new());
+
+            tail.next.store(segment, Ordering::Release);
+            *tail_guard = segment;
+
+            tail = unsafe { &*segment };
+            old_length = 0;
+        }
+
+        let contents = unsafe {
+            // SAFETY: slots at or above `length` are never read, and we hold the lock,
+            //         so nobody else is writing this slot.
+            (*tail.data[old_length].get()).write(cont) as *const T
+        };
+
+        tail.length.store(old_length + 1, Ordering::Release);
+        contents
+    }
+}
+
+// SAFETY: values are moved in from any thread (`Send`) and read from any thread (`Sync`).
+//         The raw tail pointer is only touched while holding the lock.
+unsafe impl<T: Send + Sync, const N: usize> Send for SyncArenaInner<T, N> {}
+unsafe impl<T: Send + Sync, const N: usize> Sync for SyncArenaInner<T, N> {}
+
+/// A thread-safe version of [`crate::ArenaRef`], pointing to a value within a [`SyncArena`].
+///
+/// Like its single threaded twin, it does NOT keep the [`SyncArena`] alive. The value is read
+/// through a [`SyncArenaGuard`] from [`SyncArenaRef::try_get`], which does keep it alive for as
+/// long as the guard is held, so no other thread can free it while you are reading.
+pub struct SyncArenaRef<T: Sized, const N: usize> {
+    arena: Weak<SyncArenaInner<T, N>>,
+
+    // SAFETY: ptr MUST be contained within the tree of parent! And
+    //         it will be valid as long as arena is valid
+    ptr: *const T,
+}
+
+unsafe impl<T: Send + Sync, const N: usize> Send for SyncArenaRef<T, N> {}
+unsafe impl<T: Send + Sync, const N: usize> Sync for SyncArenaRef<T, N> {}
+
+impl<T, const N: usize> SyncArenaRef<T, N> {
+    ///  Try to retrieve the contained value, keeping the [`SyncArena`] alive while the returned
+    ///  guard is held.
+    ///
+    ///  [`None`] corresponds to the parent [`SyncArena`] no longer existing
+    pub fn try_get(&self) -> Option<SyncArenaGuard<T, N>> {
+        self.arena.upgrade().map(|arena| SyncArenaGuard {
+            _arena: arena,
+            ptr: self.ptr,
+        })
+    }
+
+    ///  Try to retrieve the parent [`SyncArena`]. Returns [`None`] when it is no longer alive.
+    pub fn get_arena(&self) -> Option<SyncArena<T, N>> {
+        self.arena.upgrade().map(|inner| SyncArena { inner })
+    }
+
+    /// Test if two [`SyncArenaRef`]s are pointing to the same values in the same [`SyncArena`]s.
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        self.ptr.eq(&other.ptr) && self.arena.ptr_eq(&other.arena)
+    }
+}
+
+impl<T, const N: usize> Clone for SyncArenaRef<T, N> {
+    fn clone(&self) -> Self {
+        SyncArenaRef {
+            arena: self.arena.clone(),
+            ptr: self.ptr,
+        }
+    }
+}
+
+impl<T: Debug, const N: usize> Debug for SyncArenaRef<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.try_get() {
+            Some(value) => f.debug_tuple("SyncArenaRef").field(&*value).finish(),
+            None => f.debug_tuple("SyncArenaRef").field(&"<dead arena>").finish(),
+        }
+    }
+}
+
+impl<T: Display, const N: usize> Display for SyncArenaRef<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.try_get() {
+            Some(value) => std::fmt::Display::fmt(&*value, f),
+            None => write!(f, "<dead arena reference>"),
+        }
+    }
+}
+
+/// A value within a [`SyncArena`], read through [`SyncArenaRef::try_get`].
+///
+/// Holds a strong handle to the [`SyncArena`], so the value stays alive until the guard is
+/// dropped even if every [`SyncArena`] is dropped in the meantime.
+pub struct SyncArenaGuard<T: Sized, const N: usize> {
+    _arena: Arc<SyncArenaInner<T, N>>,
+
+    // SAFETY: points within `_arena`, which is kept alive by the guard.
+    ptr: *const T,
+}
+
+unsafe impl<T: Send + Sync, const N: usize> Send for SyncArenaGuard<T, N> {}
+unsafe impl<T: Send + Sync, const N: usize> Sync for SyncArenaGuard<T, N> {}
+
+impl<T, const N: usize> Deref for SyncArenaGuard<T, N> {
+    type Target = T;
+
+    fn deref(&self) -> &Self::Target {
+        unsafe { &*self.ptr }
+    }
+}
+
+impl<T: Debug, const N: usize> Debug for SyncArenaGuard<T, N> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        Debug::fmt(&**self, f)
+    }
+}
+
+/// A typed memory arena that you can pass between threads like an [`Arc`].
+///
+/// Allocation takes a short lock around the bump, everything else (reading through a
+/// [`SyncArenaRef`], iterating) is lock-free.
+///
+/// Example:
+/// ```
+/// use light_rc_arena::*;
+///
+/// let arena = SyncArena::<i32>::new();
+///
+/// let handles: Vec<_> = (0..4)
+///     .map(|i| {
+///         let arena = arena.clone();
+///         std::thread::spawn(move || *arena.alloc(i).try_get().unwrap())
+///     })
+///     .collect();
+///
+/// for handle in handles {
+///     handle.join().unwrap();
+/// }
+///
+/// assert_eq!(arena.iter().count(), 4);
+/// ```
+pub struct SyncArena<T: Sized, const N: usize = 64> {
+    inner: Arc<SyncArenaInner<T, N>>,
+}
+
+impl<T, const N: usize> SyncArena<T, N> {
+    /// Create a new SyncArena
+    pub fn new() -> SyncArena<T, N> {
+        assert!(N > 0, "Using zero for segment size is illegal!");
+
+        let head = Box::into_raw(SyncSegment::new());
+
+        SyncArena {
+            inner: Arc::new(SyncArenaInner {
+                tail: Mutex::new(head),
+                head,
+            }),
+        }
+    }
+
+    /// Move an object into the arena, and return a [`SyncArenaRef`] to its new location.
+    #[inline]
+    pub fn alloc(&self, cont: T) -> SyncArenaRef<T, N> {
+        SyncArenaRef {
+            arena: Arc::downgrade(&self.inner),
+            ptr: self.inner.alloc(cont),
+        }
+    }
+
+    /// Create an iterator over every element in the [`SyncArena`], yielding [`SyncArenaRef`]s.
+    ///
+    /// Values allocated by other threads while iterating may or may not be visited.
+    pub fn iter(&self) -> SyncArenaIterator<T, N> {
+        SyncArenaIterator {
+            segment: self.inner.head,
+            arena_inner: self.inner.clone(),
+            pos: 0,
+        }
+    }
+}
+
+impl<T, const N: usize> Clone for SyncArena<T, N> {
+    fn clone(&self) -> Self {
+        SyncArena {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T, const N: usize> Default for SyncArena<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T, const N: usize> PartialEq for SyncArena<T, N> {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+/// An iterator over every element in the [`SyncArena`], yielding [`SyncArenaRef`].
+pub struct SyncArenaIterator<T: Sized, const N: usize> {
+    arena_inner: Arc<SyncArenaInner<T, N>>,
+    pos: usize,
+    segment: *const SyncSegment<T, N>,
+}
+
+unsafe impl<T: Send + Sync, const N: usize> Send for SyncArenaIterator<T, N> {}
+unsafe impl<T: Send + Sync, const N: usize> Sync for SyncArenaIterator<T, N> {}
+
+impl<T, const N: usize> Iterator for SyncArenaIterator<T, N> {
+    type Item = SyncArenaRef<T, N>;
+    fn next(&mut self) -> Option<Self::Item> {
+        unsafe {
+            // SAFETY: `arena_inner` keeps every segment of the chain alive.
+            if self.pos >= (*self.segment).length.load(Ordering::Acquire) {
+                if self.pos < N {
+                    return None;
+                }
+
+                let next = (*self.segment).next.load(Ordering::Acquire);
+                if next.is_null() || (*next).length.load(Ordering::Acquire) == 0 {
+                    return None;
+                }
+
+                self.segment = next;
+                self.pos = 0;
+            }
+
+            let ptr = (*(*self.segment).data[self.pos].get()).as_ptr();
+            self.pos += 1;
+
+            Some(SyncArenaRef {
+                arena: Arc::downgrade(&self.arena_inner),
+                ptr,
+            })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicU32;
+
+    #[test]
+    fn basic_usage() {
+        let arena: SyncArena<AtomicU32, 8> = SyncArena::new();
+        for i in 0..128 {
+            let r = arena.alloc(AtomicU32::new(i));
+            let r = r.try_get().unwrap();
+            assert_eq!(r.load(Ordering::Relaxed), i);
+
+            r.store(1, Ordering::Relaxed);
+            assert_eq!(r.load(Ordering::Relaxed), 1);
+        }
+
+        let r = arena.alloc(AtomicU32::new(1));
+        let value = r.try_get().unwrap();
+        drop(arena);
+
+        // The guard keeps the arena alive on its own.
+        assert_eq!(value.load(Ordering::Relaxed), 1);
+        assert!(r.try_get().is_some());
+
+        drop(value);
+        assert!(r.try_get().is_none());
+    }
+
+    #[test]
+    fn alloc_from_many_threads() {
+        let arena: SyncArena<usize, 16> = SyncArena::new();
+
+        std::thread::scope(|scope| {
+            for t in 0..8 {
+                let arena = arena.clone();
+                scope.spawn(move || {
+                    for i in 0..1000 {
+                        let r = arena.alloc(t * 1000 + i);
+                        assert_eq!(*r.try_get().unwrap(), t * 1000 + i);
+                    }
+                });
+            }
+
+            // Readers run alongside the writers.
+            for _ in 0..2 {
+                let arena = arena.clone();
+                scope.spawn(move || {
+                    for _ in 0..50 {
+                        let _ = arena.iter().count();
+                    }
+                });
+            }
+        });
+
+        let mut values: Vec<usize> = arena.iter().map(|x| *x.try_get().unwrap()).collect();
+        values.sort();
+        assert_eq!(values, (0..8000).collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn refs_cross_threads() {
+        let arena: SyncArena<String> = SyncArena::new();
+        let r = arena.alloc("hello".to_string());
+
+        let len = std::thread::spawn(move || r.try_get().unwrap().len())
+            .join()
+            .unwrap();
+        assert_eq!(len, 5);
+    }
+}